@@ -1,16 +1,124 @@
 use std::env;
+#[cfg(feature = "embedded-resources")]
+use std::fs;
+#[cfg(feature = "embedded-resources")]
+use std::path::Path;
 use std::path::PathBuf;
 
+/// Where the verovio library comes from and how to use it.
+struct Verovio {
+    /// Directories to search for `c_wrapper.h`.
+    include_dirs: Vec<PathBuf>,
+    /// `cargo:` instructions linking the library, emitted after the shim
+    /// so that the shim comes first on the linker command line.
+    link: Vec<String>,
+}
+
 fn main() {
-    let mut config = cmake::Config::new("verovio/cmake");
     let target = env::var("TARGET").expect("TARGET");
+    println!("cargo:rerun-if-env-changed=VEROVIO_LIB_DIR");
+    println!("cargo:rerun-if-env-changed=VEROVIO_INCLUDE_DIR");
+    println!("cargo:rerun-if-env-changed=VEROVIO_STATIC");
+
+    let verovio = match env::var_os("VEROVIO_LIB_DIR") {
+        Some(lib_dir) => prebuilt(PathBuf::from(lib_dir)),
+        #[cfg(feature = "system")]
+        None => system(),
+        #[cfg(not(feature = "system"))]
+        None => vendored(&target),
+    };
+
+    let mut shim = cc::Build::new();
+    shim.cpp(true)
+        .std("c++17")
+        .file("shim/vrv_shim.cpp")
+        .include("shim")
+        .includes(&verovio.include_dirs);
+    if target.contains("windows") {
+        // With /EHc MSVC assumes extern "C" functions never throw,
+        // which would optimize the catch blocks of the shim away.
+        shim.flag("/EHc-");
+    }
+    if target.contains("android") {
+        shim.cpp_link_stdlib(None);
+    }
+    shim.compile("vrv_shim");
+    println!("cargo:rerun-if-changed=shim/vrv_shim.h");
+    println!("cargo:rerun-if-changed=shim/vrv_shim.cpp");
+
+    for line in &verovio.link {
+        println!("{}", line);
+    }
+
+    #[cfg(feature = "embedded-resources")]
+    embed_resources();
+
+    #[cfg(feature = "bindgen")]
+    {
+        let bindings = bindgen::Builder::default()
+            .header("shim/vrv_shim.h")
+            .clang_arg("-xc++")
+            .generate()
+            .expect("Unable to generate bindings");
+
+        let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
+        bindings
+            .write_to_file(out_path.join("bindings.rs"))
+            .expect("Couldn't write bindings!");
+    }
+}
+
+/// A library built elsewhere, found through `VEROVIO_LIB_DIR` and `VEROVIO_INCLUDE_DIR`.
+fn prebuilt(lib_dir: PathBuf) -> Verovio {
+    let include_dir = env::var_os("VEROVIO_INCLUDE_DIR")
+        .expect("VEROVIO_INCLUDE_DIR must be set together with VEROVIO_LIB_DIR");
+    let kind = if env::var_os("VEROVIO_STATIC").is_some() {
+        "static="
+    } else {
+        ""
+    };
+    Verovio {
+        include_dirs: vec![PathBuf::from(include_dir)],
+        link: vec![
+            format!("cargo:rustc-link-search=native={}", lib_dir.display()),
+            format!("cargo:rustc-link-lib={}verovio", kind),
+        ],
+    }
+}
+
+/// An installed library, found through pkg-config.
+#[cfg(feature = "system")]
+fn system() -> Verovio {
+    let library = pkg_config::Config::new()
+        .cargo_metadata(false)
+        .probe("verovio")
+        .expect("Unable to find verovio with pkg-config");
+    let mut include_dirs = vec![];
+    for dir in library.include_paths {
+        include_dirs.push(dir.join("verovio"));
+        include_dirs.push(dir);
+    }
+    let mut link = vec![];
+    for dir in library.link_paths {
+        link.push(format!("cargo:rustc-link-search=native={}", dir.display()));
+    }
+    for lib in library.libs {
+        link.push(format!("cargo:rustc-link-lib={}", lib));
+    }
+    Verovio { include_dirs, link }
+}
+
+/// The `verovio` submodule, built with CMake.
+#[cfg(not(feature = "system"))]
+fn vendored(target: &str) -> Verovio {
+    let mut config = cmake::Config::new("verovio/cmake");
     config
         .define("CMAKE_BUILD_TYPE", "Release")
         .define("BUILD_SHARED_LIBS", "OFF")
-        .define("NO_EDIT_SUPPORT", "ON")
-        .define("NO_PAE_SUPPORT", "ON")
-        .define("NO_HUMDRUM_SUPPORT", "ON")
-        .define("NO_ABC_SUPPORT", "ON");
+        .define("NO_EDIT_SUPPORT", disabled("EDIT"))
+        .define("NO_PAE_SUPPORT", disabled("PAE"))
+        .define("NO_HUMDRUM_SUPPORT", disabled("HUMDRUM"))
+        .define("NO_ABC_SUPPORT", disabled("ABC"));
     if target.contains("windows") {
         config
             .define("CMAKE_CXX_FLAGS", "/utf-8 /MP")
@@ -29,26 +137,89 @@ fn main() {
             .define("BUILD_AS_ANDROID_LIBRARY", "ON")
             .define("ANDROID_PLATFORM", "android-21")
             .define("ANDROID_STL", "c++_static");
-        println!("cargo:rustc-link-lib=c++_static");
-        println!("cargo:rustc-link-lib=c++abi");
     } else {
         config.define("BUILD_AS_LIBRARY", "ON");
     }
     let dst = config.build();
 
-    println!("cargo:rustc-link-search=native={}/lib", dst.display());
-    println!("cargo:rustc-link-lib=static=verovio");
+    let mut link = vec![
+        format!("cargo:rustc-link-search=native={}/lib", dst.display()),
+        "cargo:rustc-link-lib=static=verovio".into(),
+    ];
+    if target.contains("android") {
+        link.push("cargo:rustc-link-lib=c++_static".into());
+        link.push("cargo:rustc-link-lib=c++abi".into());
+    }
+    Verovio {
+        include_dirs: vec![PathBuf::from("verovio/tools")],
+        link,
+    }
+}
+
+/// Generate `resources.rs`, which embeds every file of verovio's `data` directory.
+#[cfg(feature = "embedded-resources")]
+fn embed_resources() {
+    println!("cargo:rerun-if-env-changed=VEROVIO_DATA_DIR");
+    let data_dir = env::var_os("VEROVIO_DATA_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("verovio/data"));
+    let data_dir = data_dir
+        .canonicalize()
+        .expect("Unable to find the verovio data directory");
+    println!("cargo:rerun-if-changed={}", data_dir.display());
+
+    let mut files = vec![];
+    collect_files(&data_dir, &mut files);
+    files.sort();
 
-    let bindings = bindgen::Builder::default()
-        .header("verovio/tools/c_wrapper.h")
-        .clang_arg("-xc++")
-        .clang_arg("-Iverovio/src")
-        .clang_arg("-Iverovio/include")
-        .generate()
-        .expect("Unable to generate bindings");
+    // FNV-1a over names and contents, so that a changed tree gets a new directory at runtime.
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let mut entries = String::new();
+    for file in &files {
+        let name = file
+            .strip_prefix(&data_dir)
+            .unwrap()
+            .components()
+            .map(|c| c.as_os_str().to_str().expect("Non UTF-8 resource path"))
+            .collect::<Vec<_>>()
+            .join("/");
+        let content = fs::read(file).expect("Unable to read resource");
+        for byte in name.bytes().chain([0]).chain(content) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        entries += &format!("    ({:?}, include_bytes!({:?})),\n", name, file);
+    }
 
     let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
-    bindings
-        .write_to_file(out_path.join("bindings.rs"))
-        .expect("Couldn't write bindings!");
+    fs::write(
+        out_path.join("resources.rs"),
+        format!(
+            "const RESOURCES_HASH: u64 = {:#x};\n\nstatic RESOURCES: &[(&str, &[u8])] = &[\n{}];\n",
+            hash, entries
+        ),
+    )
+    .expect("Couldn't write resources!");
+}
+
+#[cfg(feature = "embedded-resources")]
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).expect("Unable to read resource directory") {
+        let path = entry.expect("Unable to read resource directory").path();
+        if path.is_dir() {
+            collect_files(&path, files);
+        } else {
+            files.push(path);
+        }
+    }
+}
+
+/// Value of the verovio `NO_*_SUPPORT` define for a cargo feature.
+#[cfg(not(feature = "system"))]
+fn disabled(feature: &str) -> &'static str {
+    if env::var_os(format!("CARGO_FEATURE_{}", feature)).is_some() {
+        "OFF"
+    } else {
+        "ON"
+    }
 }
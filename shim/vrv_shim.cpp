@@ -0,0 +1,326 @@
+#include "vrv_shim.h"
+
+#include <exception>
+#include <string>
+
+#include "c_wrapper.h"
+
+namespace {
+
+thread_local std::string lastError;
+
+template <typename F> int guard(F &&f)
+{
+    try {
+        f();
+        return VRV_SHIM_OK;
+    }
+    catch (const std::exception &e) {
+        lastError = e.what();
+    }
+    catch (...) {
+        lastError = "unknown C++ exception";
+    }
+    return VRV_SHIM_EXCEPTION;
+}
+
+} // namespace
+
+extern "C" {
+
+const char *vrvShim_lastError()
+{
+    return lastError.c_str();
+}
+
+int vrvShim_constructor(void **out)
+{
+    return guard([&] { *out = vrvToolkit_constructor(); });
+}
+
+int vrvShim_constructorNoResource(void **out)
+{
+    return guard([&] { *out = vrvToolkit_constructorNoResource(); });
+}
+
+int vrvShim_constructorResourcePath(const char *resourcePath, void **out)
+{
+    return guard([&] { *out = vrvToolkit_constructorResourcePath(resourcePath); });
+}
+
+int vrvShim_destructor(void *tkPtr)
+{
+    return guard([&] { vrvToolkit_destructor(tkPtr); });
+}
+
+int vrvShim_edit(void *tkPtr, const char *editorAction, bool *out)
+{
+    return guard([&] { *out = vrvToolkit_edit(tkPtr, editorAction); });
+}
+
+int vrvShim_editInfo(void *tkPtr, const char **out)
+{
+    return guard([&] { *out = vrvToolkit_editInfo(tkPtr); });
+}
+
+int vrvShim_getAvailableOptions(void *tkPtr, const char **out)
+{
+    return guard([&] { *out = vrvToolkit_getAvailableOptions(tkPtr); });
+}
+
+int vrvShim_getDefaultOptions(void *tkPtr, const char **out)
+{
+    return guard([&] { *out = vrvToolkit_getDefaultOptions(tkPtr); });
+}
+
+int vrvShim_getDescriptiveFeatures(void *tkPtr, const char *options, const char **out)
+{
+    return guard([&] { *out = vrvToolkit_getDescriptiveFeatures(tkPtr, options); });
+}
+
+int vrvShim_getElementAttr(void *tkPtr, const char *xmlId, const char **out)
+{
+    return guard([&] { *out = vrvToolkit_getElementAttr(tkPtr, xmlId); });
+}
+
+int vrvShim_getElementsAtTime(void *tkPtr, int millisec, const char **out)
+{
+    return guard([&] { *out = vrvToolkit_getElementsAtTime(tkPtr, millisec); });
+}
+
+int vrvShim_getExpansionIdsForElement(void *tkPtr, const char *xmlId, const char **out)
+{
+    return guard([&] { *out = vrvToolkit_getExpansionIdsForElement(tkPtr, xmlId); });
+}
+
+int vrvShim_getHumdrum(void *tkPtr, const char **out)
+{
+    return guard([&] { *out = vrvToolkit_getHumdrum(tkPtr); });
+}
+
+int vrvShim_getHumdrumFile(void *tkPtr, const char *filename, bool *out)
+{
+    return guard([&] { *out = vrvToolkit_getHumdrumFile(tkPtr, filename); });
+}
+
+int vrvShim_convertHumdrumToHumdrum(void *tkPtr, const char *humdrumData, const char **out)
+{
+    return guard([&] { *out = vrvToolkit_convertHumdrumToHumdrum(tkPtr, humdrumData); });
+}
+
+int vrvShim_convertHumdrumToMIDI(void *tkPtr, const char *humdrumData, const char **out)
+{
+    return guard([&] { *out = vrvToolkit_convertHumdrumToMIDI(tkPtr, humdrumData); });
+}
+
+int vrvShim_convertMEIToHumdrum(void *tkPtr, const char *meiData, const char **out)
+{
+    return guard([&] { *out = vrvToolkit_convertMEIToHumdrum(tkPtr, meiData); });
+}
+
+int vrvShim_getID(void *tkPtr, const char **out)
+{
+    return guard([&] { *out = vrvToolkit_getID(tkPtr); });
+}
+
+int vrvShim_getLog(void *tkPtr, const char **out)
+{
+    return guard([&] { *out = vrvToolkit_getLog(tkPtr); });
+}
+
+int vrvShim_getMEI(void *tkPtr, const char *options, const char **out)
+{
+    return guard([&] { *out = vrvToolkit_getMEI(tkPtr, options); });
+}
+
+int vrvShim_getMIDIValuesForElement(void *tkPtr, const char *xmlId, const char **out)
+{
+    return guard([&] { *out = vrvToolkit_getMIDIValuesForElement(tkPtr, xmlId); });
+}
+
+int vrvShim_getNotatedIdForElement(void *tkPtr, const char *xmlId, const char **out)
+{
+    return guard([&] { *out = vrvToolkit_getNotatedIdForElement(tkPtr, xmlId); });
+}
+
+int vrvShim_getOptions(void *tkPtr, const char **out)
+{
+    return guard([&] { *out = vrvToolkit_getOptions(tkPtr); });
+}
+
+int vrvShim_getOptionUsageString(void *tkPtr, const char **out)
+{
+    return guard([&] { *out = vrvToolkit_getOptionUsageString(tkPtr); });
+}
+
+int vrvShim_getPageCount(void *tkPtr, int *out)
+{
+    return guard([&] { *out = vrvToolkit_getPageCount(tkPtr); });
+}
+
+int vrvShim_getPageWithElement(void *tkPtr, const char *xmlId, int *out)
+{
+    return guard([&] { *out = vrvToolkit_getPageWithElement(tkPtr, xmlId); });
+}
+
+int vrvShim_getResourcePath(void *tkPtr, const char **out)
+{
+    return guard([&] { *out = vrvToolkit_getResourcePath(tkPtr); });
+}
+
+int vrvShim_getScale(void *tkPtr, int *out)
+{
+    return guard([&] { *out = vrvToolkit_getScale(tkPtr); });
+}
+
+int vrvShim_getTimeForElement(void *tkPtr, const char *xmlId, double *out)
+{
+    return guard([&] { *out = vrvToolkit_getTimeForElement(tkPtr, xmlId); });
+}
+
+int vrvShim_getTimesForElement(void *tkPtr, const char *xmlId, const char **out)
+{
+    return guard([&] { *out = vrvToolkit_getTimesForElement(tkPtr, xmlId); });
+}
+
+int vrvShim_getVersion(void *tkPtr, const char **out)
+{
+    return guard([&] { *out = vrvToolkit_getVersion(tkPtr); });
+}
+
+int vrvShim_loadData(void *tkPtr, const char *data, bool *out)
+{
+    return guard([&] { *out = vrvToolkit_loadData(tkPtr, data); });
+}
+
+int vrvShim_loadFile(void *tkPtr, const char *filename, bool *out)
+{
+    return guard([&] { *out = vrvToolkit_loadFile(tkPtr, filename); });
+}
+
+int vrvShim_loadZipDataBuffer(void *tkPtr, const unsigned char *data, int length, bool *out)
+{
+    return guard([&] { *out = vrvToolkit_loadZipDataBuffer(tkPtr, data, length); });
+}
+
+int vrvShim_redoLayout(void *tkPtr, const char *c_options)
+{
+    return guard([&] { vrvToolkit_redoLayout(tkPtr, c_options); });
+}
+
+int vrvShim_redoPagePitchPosLayout(void *tkPtr)
+{
+    return guard([&] { vrvToolkit_redoPagePitchPosLayout(tkPtr); });
+}
+
+int vrvShim_renderData(void *tkPtr, const char *data, const char *options, const char **out)
+{
+    return guard([&] { *out = vrvToolkit_renderData(tkPtr, data, options); });
+}
+
+int vrvShim_renderToExpansionMap(void *tkPtr, const char **out)
+{
+    return guard([&] { *out = vrvToolkit_renderToExpansionMap(tkPtr); });
+}
+
+int vrvShim_renderToExpansionMapFile(void *tkPtr, const char *filename, bool *out)
+{
+    return guard([&] { *out = vrvToolkit_renderToExpansionMapFile(tkPtr, filename); });
+}
+
+int vrvShim_renderToMIDI(void *tkPtr, const char **out)
+{
+    return guard([&] { *out = vrvToolkit_renderToMIDI(tkPtr); });
+}
+
+int vrvShim_renderToMIDIFile(void *tkPtr, const char *filename, bool *out)
+{
+    return guard([&] { *out = vrvToolkit_renderToMIDIFile(tkPtr, filename); });
+}
+
+int vrvShim_renderToPAE(void *tkPtr, const char **out)
+{
+    return guard([&] { *out = vrvToolkit_renderToPAE(tkPtr); });
+}
+
+int vrvShim_renderToPAEFile(void *tkPtr, const char *filename, bool *out)
+{
+    return guard([&] { *out = vrvToolkit_renderToPAEFile(tkPtr, filename); });
+}
+
+int vrvShim_renderToSVG(void *tkPtr, int pageNo, bool xmlDeclaration, const char **out)
+{
+    return guard([&] { *out = vrvToolkit_renderToSVG(tkPtr, pageNo, xmlDeclaration); });
+}
+
+int vrvShim_renderToSVGFile(void *tkPtr, const char *filename, int pageNo, bool *out)
+{
+    return guard([&] { *out = vrvToolkit_renderToSVGFile(tkPtr, filename, pageNo); });
+}
+
+int vrvShim_renderToTimemap(void *tkPtr, const char *c_options, const char **out)
+{
+    return guard([&] { *out = vrvToolkit_renderToTimemap(tkPtr, c_options); });
+}
+
+int vrvShim_renderToTimemapFile(void *tkPtr, const char *filename, const char *c_options, bool *out)
+{
+    return guard([&] { *out = vrvToolkit_renderToTimemapFile(tkPtr, filename, c_options); });
+}
+
+int vrvShim_resetOptions(void *tkPtr)
+{
+    return guard([&] { vrvToolkit_resetOptions(tkPtr); });
+}
+
+int vrvShim_resetXmlIdSeed(void *tkPtr, int seed)
+{
+    return guard([&] { vrvToolkit_resetXmlIdSeed(tkPtr, seed); });
+}
+
+int vrvShim_saveFile(void *tkPtr, const char *filename, const char *c_options, bool *out)
+{
+    return guard([&] { *out = vrvToolkit_saveFile(tkPtr, filename, c_options); });
+}
+
+int vrvShim_select(void *tkPtr, const char *selection, bool *out)
+{
+    return guard([&] { *out = vrvToolkit_select(tkPtr, selection); });
+}
+
+int vrvShim_setInputFrom(void *tkPtr, const char *inputFrom, bool *out)
+{
+    return guard([&] { *out = vrvToolkit_setInputFrom(tkPtr, inputFrom); });
+}
+
+int vrvShim_setOptions(void *tkPtr, const char *options, bool *out)
+{
+    return guard([&] { *out = vrvToolkit_setOptions(tkPtr, options); });
+}
+
+int vrvShim_setOutputTo(void *tkPtr, const char *outputTo, bool *out)
+{
+    return guard([&] { *out = vrvToolkit_setOutputTo(tkPtr, outputTo); });
+}
+
+int vrvShim_setResourcePath(void *tkPtr, const char *path, bool *out)
+{
+    return guard([&] { *out = vrvToolkit_setResourcePath(tkPtr, path); });
+}
+
+int vrvShim_setScale(void *tkPtr, int scale, bool *out)
+{
+    return guard([&] { *out = vrvToolkit_setScale(tkPtr, scale); });
+}
+
+int vrvShim_validatePAE(void *tkPtr, const char *data, const char **out)
+{
+    return guard([&] { *out = vrvToolkit_validatePAE(tkPtr, data); });
+}
+
+int vrvShim_validatePAEFile(void *tkPtr, const char *filename, const char **out)
+{
+    return guard([&] { *out = vrvToolkit_validatePAEFile(tkPtr, filename); });
+}
+
+} // extern "C"
@@ -0,0 +1,90 @@
+#ifndef __VRV_SHIM_H__
+#define __VRV_SHIM_H__
+
+#include <stdbool.h>
+
+/*
+ * Exception-safe wrappers around the functions of verovio's c_wrapper.h.
+ *
+ * Every wrapper returns VRV_SHIM_OK on success and VRV_SHIM_EXCEPTION if a C++
+ * exception escaped the wrapped call. The wrapped return value, if any, is
+ * written to the trailing `out` parameter. The message of the last exception
+ * caught on the current thread is available through vrvShim_lastError.
+ */
+
+#define VRV_SHIM_OK 0
+#define VRV_SHIM_EXCEPTION 1
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+const char *vrvShim_lastError();
+
+void enableLog(bool value);
+void enableLogToBuffer(bool value);
+
+int vrvShim_constructor(void **out);
+int vrvShim_constructorNoResource(void **out);
+int vrvShim_constructorResourcePath(const char *resourcePath, void **out);
+int vrvShim_destructor(void *tkPtr);
+int vrvShim_edit(void *tkPtr, const char *editorAction, bool *out);
+int vrvShim_editInfo(void *tkPtr, const char **out);
+int vrvShim_getAvailableOptions(void *tkPtr, const char **out);
+int vrvShim_getDefaultOptions(void *tkPtr, const char **out);
+int vrvShim_getDescriptiveFeatures(void *tkPtr, const char *options, const char **out);
+int vrvShim_getElementAttr(void *tkPtr, const char *xmlId, const char **out);
+int vrvShim_getElementsAtTime(void *tkPtr, int millisec, const char **out);
+int vrvShim_getExpansionIdsForElement(void *tkPtr, const char *xmlId, const char **out);
+int vrvShim_getHumdrum(void *tkPtr, const char **out);
+int vrvShim_getHumdrumFile(void *tkPtr, const char *filename, bool *out);
+int vrvShim_convertHumdrumToHumdrum(void *tkPtr, const char *humdrumData, const char **out);
+int vrvShim_convertHumdrumToMIDI(void *tkPtr, const char *humdrumData, const char **out);
+int vrvShim_convertMEIToHumdrum(void *tkPtr, const char *meiData, const char **out);
+int vrvShim_getID(void *tkPtr, const char **out);
+int vrvShim_getLog(void *tkPtr, const char **out);
+int vrvShim_getMEI(void *tkPtr, const char *options, const char **out);
+int vrvShim_getMIDIValuesForElement(void *tkPtr, const char *xmlId, const char **out);
+int vrvShim_getNotatedIdForElement(void *tkPtr, const char *xmlId, const char **out);
+int vrvShim_getOptions(void *tkPtr, const char **out);
+int vrvShim_getOptionUsageString(void *tkPtr, const char **out);
+int vrvShim_getPageCount(void *tkPtr, int *out);
+int vrvShim_getPageWithElement(void *tkPtr, const char *xmlId, int *out);
+int vrvShim_getResourcePath(void *tkPtr, const char **out);
+int vrvShim_getScale(void *tkPtr, int *out);
+int vrvShim_getTimeForElement(void *tkPtr, const char *xmlId, double *out);
+int vrvShim_getTimesForElement(void *tkPtr, const char *xmlId, const char **out);
+int vrvShim_getVersion(void *tkPtr, const char **out);
+int vrvShim_loadData(void *tkPtr, const char *data, bool *out);
+int vrvShim_loadFile(void *tkPtr, const char *filename, bool *out);
+int vrvShim_loadZipDataBuffer(void *tkPtr, const unsigned char *data, int length, bool *out);
+int vrvShim_redoLayout(void *tkPtr, const char *c_options);
+int vrvShim_redoPagePitchPosLayout(void *tkPtr);
+int vrvShim_renderData(void *tkPtr, const char *data, const char *options, const char **out);
+int vrvShim_renderToExpansionMap(void *tkPtr, const char **out);
+int vrvShim_renderToExpansionMapFile(void *tkPtr, const char *filename, bool *out);
+int vrvShim_renderToMIDI(void *tkPtr, const char **out);
+int vrvShim_renderToMIDIFile(void *tkPtr, const char *filename, bool *out);
+int vrvShim_renderToPAE(void *tkPtr, const char **out);
+int vrvShim_renderToPAEFile(void *tkPtr, const char *filename, bool *out);
+int vrvShim_renderToSVG(void *tkPtr, int pageNo, bool xmlDeclaration, const char **out);
+int vrvShim_renderToSVGFile(void *tkPtr, const char *filename, int pageNo, bool *out);
+int vrvShim_renderToTimemap(void *tkPtr, const char *c_options, const char **out);
+int vrvShim_renderToTimemapFile(void *tkPtr, const char *filename, const char *c_options, bool *out);
+int vrvShim_resetOptions(void *tkPtr);
+int vrvShim_resetXmlIdSeed(void *tkPtr, int seed);
+int vrvShim_saveFile(void *tkPtr, const char *filename, const char *c_options, bool *out);
+int vrvShim_select(void *tkPtr, const char *selection, bool *out);
+int vrvShim_setInputFrom(void *tkPtr, const char *inputFrom, bool *out);
+int vrvShim_setOptions(void *tkPtr, const char *options, bool *out);
+int vrvShim_setOutputTo(void *tkPtr, const char *outputTo, bool *out);
+int vrvShim_setResourcePath(void *tkPtr, const char *path, bool *out);
+int vrvShim_setScale(void *tkPtr, int scale, bool *out);
+int vrvShim_validatePAE(void *tkPtr, const char *data, const char **out);
+int vrvShim_validatePAEFile(void *tkPtr, const char *filename, const char **out);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,5 @@
+//! Worker process for [`verovior::IsolatedToolkit`].
+
+fn main() -> anyhow::Result<()> {
+    verovior::isolated::run_worker()
+}
@@ -0,0 +1,391 @@
+/* automatically generated by rust-bindgen 0.72.0 */
+
+pub const VRV_SHIM_OK: u32 = 0;
+pub const VRV_SHIM_EXCEPTION: u32 = 1;
+unsafe extern "C" {
+    pub fn vrvShim_lastError() -> *const ::std::os::raw::c_char;
+}
+unsafe extern "C" {
+    pub fn enableLog(value: bool);
+}
+unsafe extern "C" {
+    pub fn enableLogToBuffer(value: bool);
+}
+unsafe extern "C" {
+    pub fn vrvShim_constructor(out: *mut *mut ::std::os::raw::c_void) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_constructorNoResource(
+        out: *mut *mut ::std::os::raw::c_void,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_constructorResourcePath(
+        resourcePath: *const ::std::os::raw::c_char,
+        out: *mut *mut ::std::os::raw::c_void,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_destructor(tkPtr: *mut ::std::os::raw::c_void) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_edit(
+        tkPtr: *mut ::std::os::raw::c_void,
+        editorAction: *const ::std::os::raw::c_char,
+        out: *mut bool,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_editInfo(
+        tkPtr: *mut ::std::os::raw::c_void,
+        out: *mut *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_getAvailableOptions(
+        tkPtr: *mut ::std::os::raw::c_void,
+        out: *mut *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_getDefaultOptions(
+        tkPtr: *mut ::std::os::raw::c_void,
+        out: *mut *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_getDescriptiveFeatures(
+        tkPtr: *mut ::std::os::raw::c_void,
+        options: *const ::std::os::raw::c_char,
+        out: *mut *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_getElementAttr(
+        tkPtr: *mut ::std::os::raw::c_void,
+        xmlId: *const ::std::os::raw::c_char,
+        out: *mut *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_getElementsAtTime(
+        tkPtr: *mut ::std::os::raw::c_void,
+        millisec: ::std::os::raw::c_int,
+        out: *mut *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_getExpansionIdsForElement(
+        tkPtr: *mut ::std::os::raw::c_void,
+        xmlId: *const ::std::os::raw::c_char,
+        out: *mut *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_getHumdrum(
+        tkPtr: *mut ::std::os::raw::c_void,
+        out: *mut *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_getHumdrumFile(
+        tkPtr: *mut ::std::os::raw::c_void,
+        filename: *const ::std::os::raw::c_char,
+        out: *mut bool,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_convertHumdrumToHumdrum(
+        tkPtr: *mut ::std::os::raw::c_void,
+        humdrumData: *const ::std::os::raw::c_char,
+        out: *mut *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_convertHumdrumToMIDI(
+        tkPtr: *mut ::std::os::raw::c_void,
+        humdrumData: *const ::std::os::raw::c_char,
+        out: *mut *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_convertMEIToHumdrum(
+        tkPtr: *mut ::std::os::raw::c_void,
+        meiData: *const ::std::os::raw::c_char,
+        out: *mut *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_getID(
+        tkPtr: *mut ::std::os::raw::c_void,
+        out: *mut *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_getLog(
+        tkPtr: *mut ::std::os::raw::c_void,
+        out: *mut *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_getMEI(
+        tkPtr: *mut ::std::os::raw::c_void,
+        options: *const ::std::os::raw::c_char,
+        out: *mut *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_getMIDIValuesForElement(
+        tkPtr: *mut ::std::os::raw::c_void,
+        xmlId: *const ::std::os::raw::c_char,
+        out: *mut *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_getNotatedIdForElement(
+        tkPtr: *mut ::std::os::raw::c_void,
+        xmlId: *const ::std::os::raw::c_char,
+        out: *mut *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_getOptions(
+        tkPtr: *mut ::std::os::raw::c_void,
+        out: *mut *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_getOptionUsageString(
+        tkPtr: *mut ::std::os::raw::c_void,
+        out: *mut *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_getPageCount(
+        tkPtr: *mut ::std::os::raw::c_void,
+        out: *mut ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_getPageWithElement(
+        tkPtr: *mut ::std::os::raw::c_void,
+        xmlId: *const ::std::os::raw::c_char,
+        out: *mut ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_getResourcePath(
+        tkPtr: *mut ::std::os::raw::c_void,
+        out: *mut *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_getScale(
+        tkPtr: *mut ::std::os::raw::c_void,
+        out: *mut ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_getTimeForElement(
+        tkPtr: *mut ::std::os::raw::c_void,
+        xmlId: *const ::std::os::raw::c_char,
+        out: *mut f64,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_getTimesForElement(
+        tkPtr: *mut ::std::os::raw::c_void,
+        xmlId: *const ::std::os::raw::c_char,
+        out: *mut *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_getVersion(
+        tkPtr: *mut ::std::os::raw::c_void,
+        out: *mut *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_loadData(
+        tkPtr: *mut ::std::os::raw::c_void,
+        data: *const ::std::os::raw::c_char,
+        out: *mut bool,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_loadFile(
+        tkPtr: *mut ::std::os::raw::c_void,
+        filename: *const ::std::os::raw::c_char,
+        out: *mut bool,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_loadZipDataBuffer(
+        tkPtr: *mut ::std::os::raw::c_void,
+        data: *const ::std::os::raw::c_uchar,
+        length: ::std::os::raw::c_int,
+        out: *mut bool,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_redoLayout(
+        tkPtr: *mut ::std::os::raw::c_void,
+        c_options: *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_redoPagePitchPosLayout(
+        tkPtr: *mut ::std::os::raw::c_void,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_renderData(
+        tkPtr: *mut ::std::os::raw::c_void,
+        data: *const ::std::os::raw::c_char,
+        options: *const ::std::os::raw::c_char,
+        out: *mut *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_renderToExpansionMap(
+        tkPtr: *mut ::std::os::raw::c_void,
+        out: *mut *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_renderToExpansionMapFile(
+        tkPtr: *mut ::std::os::raw::c_void,
+        filename: *const ::std::os::raw::c_char,
+        out: *mut bool,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_renderToMIDI(
+        tkPtr: *mut ::std::os::raw::c_void,
+        out: *mut *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_renderToMIDIFile(
+        tkPtr: *mut ::std::os::raw::c_void,
+        filename: *const ::std::os::raw::c_char,
+        out: *mut bool,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_renderToPAE(
+        tkPtr: *mut ::std::os::raw::c_void,
+        out: *mut *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_renderToPAEFile(
+        tkPtr: *mut ::std::os::raw::c_void,
+        filename: *const ::std::os::raw::c_char,
+        out: *mut bool,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_renderToSVG(
+        tkPtr: *mut ::std::os::raw::c_void,
+        pageNo: ::std::os::raw::c_int,
+        xmlDeclaration: bool,
+        out: *mut *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_renderToSVGFile(
+        tkPtr: *mut ::std::os::raw::c_void,
+        filename: *const ::std::os::raw::c_char,
+        pageNo: ::std::os::raw::c_int,
+        out: *mut bool,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_renderToTimemap(
+        tkPtr: *mut ::std::os::raw::c_void,
+        c_options: *const ::std::os::raw::c_char,
+        out: *mut *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_renderToTimemapFile(
+        tkPtr: *mut ::std::os::raw::c_void,
+        filename: *const ::std::os::raw::c_char,
+        c_options: *const ::std::os::raw::c_char,
+        out: *mut bool,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_resetOptions(tkPtr: *mut ::std::os::raw::c_void) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_resetXmlIdSeed(
+        tkPtr: *mut ::std::os::raw::c_void,
+        seed: ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_saveFile(
+        tkPtr: *mut ::std::os::raw::c_void,
+        filename: *const ::std::os::raw::c_char,
+        c_options: *const ::std::os::raw::c_char,
+        out: *mut bool,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_select(
+        tkPtr: *mut ::std::os::raw::c_void,
+        selection: *const ::std::os::raw::c_char,
+        out: *mut bool,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_setInputFrom(
+        tkPtr: *mut ::std::os::raw::c_void,
+        inputFrom: *const ::std::os::raw::c_char,
+        out: *mut bool,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_setOptions(
+        tkPtr: *mut ::std::os::raw::c_void,
+        options: *const ::std::os::raw::c_char,
+        out: *mut bool,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_setOutputTo(
+        tkPtr: *mut ::std::os::raw::c_void,
+        outputTo: *const ::std::os::raw::c_char,
+        out: *mut bool,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_setResourcePath(
+        tkPtr: *mut ::std::os::raw::c_void,
+        path: *const ::std::os::raw::c_char,
+        out: *mut bool,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_setScale(
+        tkPtr: *mut ::std::os::raw::c_void,
+        scale: ::std::os::raw::c_int,
+        out: *mut bool,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_validatePAE(
+        tkPtr: *mut ::std::os::raw::c_void,
+        data: *const ::std::os::raw::c_char,
+        out: *mut *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+unsafe extern "C" {
+    pub fn vrvShim_validatePAEFile(
+        tkPtr: *mut ::std::os::raw::c_void,
+        filename: *const ::std::os::raw::c_char,
+        out: *mut *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
@@ -0,0 +1,185 @@
+//! Runtime probing of what the linked verovio library supports.
+
+use crate::{VerovioToolkit, bindings, get_primitive, get_primitive2, get_string, get_string2};
+use serde::Serialize;
+use std::sync::OnceLock;
+
+/// What the linked verovio library can do, as found by [`VerovioToolkit::capabilities`].
+///
+/// Formats are named the way verovio names them in `inputFrom` and `outputTo`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Capabilities {
+    pub input_formats: Vec<&'static str>,
+    pub output_formats: Vec<&'static str>,
+    pub editor: bool,
+    pub humdrum: bool,
+    pub pae: bool,
+    pub abc: bool,
+    pub midi: bool,
+}
+
+const MEI: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<mei xmlns="http://www.music-encoding.org/ns/mei" meiversion="5.0">
+<meiHead><fileDesc><titleStmt><title/></titleStmt><pubStmt/></fileDesc></meiHead>
+<music><body><mdiv><score>
+<scoreDef><staffGrp><staffDef n="1" lines="5" clef.shape="G" clef.line="2"/></staffGrp></scoreDef>
+<section><measure n="1"><staff n="1"><layer n="1"><note pname="c" oct="4" dur="1"/></layer></staff></measure></section>
+</score></mdiv></body></music>
+</mei>"#;
+
+const MUSIC_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<score-partwise version="3.1">
+<part-list><score-part id="P1"><part-name/></score-part></part-list>
+<part id="P1"><measure number="1">
+<attributes><divisions>1</divisions><time><beats>4</beats><beat-type>4</beat-type></time><clef><sign>G</sign><line>2</line></clef></attributes>
+<note><pitch><step>C</step><octave>4</octave></pitch><duration>4</duration><type>whole</type></note>
+</measure></part>
+</score-partwise>"#;
+
+const CMME: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Piece xmlns="http://www.cmme.org" CMMEversion="0.95">
+<GeneralData><Title/><Composer/><Editor/></GeneralData>
+<Voice><Name/><NumInVoice>1</NumInVoice></Voice>
+<MusicSection><MensuralMusic><NumVoices>1</NumVoices><Voice><VoiceNum>1</VoiceNum>
+<Clef><Appearance>C</Appearance><StaffLoc>1</StaffLoc><Pitch><LetterName>C</LetterName><OctaveNum>4</OctaveNum></Pitch></Clef>
+<Note><Type>Semibrevis</Type><LetterName>C</LetterName><OctaveNum>4</OctaveNum><Length><Num>1</Num><Den>1</Den></Length></Note>
+</Voice></MensuralMusic></MusicSection>
+</Piece>"#;
+
+const MUSEDATA: &str = "\
+(C)
+
+ID:probe
+04/01/00
+
+
+
+probe
+probe
+Group memberships: score
+score: part 1 of 1
+$  K:0   Q:1   T:1/1   C:4
+C4     4        w
+/END
+";
+
+/// A tiny document for every input format that can be probed from text.
+/// The page-based MEI of `mei-pb-serialized` is exported from the MEI sample instead.
+const SAMPLES: &[(&str, &str)] = &[
+    ("mei", MEI),
+    ("musicxml", MUSIC_XML),
+    ("abc", "X:1\nM:4/4\nL:1/4\nK:C\nC4|\n"),
+    ("pae", "@clef:G-2\n@keysig:\n@timesig:4/4\n@data:'1C\n"),
+    ("humdrum", "**kern\n*clefG2\n*M4/4\n=1\n1c\n=\n*-\n"),
+    ("mei-hum", MEI),
+    ("musicxml-hum", MUSIC_XML),
+    ("musedata", MUSEDATA),
+    ("darms", "!G 4W /"),
+    ("volpiano", "1---c--d---3"),
+    (
+        "esac",
+        "CUT[probe]\nKEY[probe 4 C 4/4]\nMEL[1 2 3 4 //]\n>>\n",
+    ),
+    ("cmme.xml", CMME),
+];
+
+type Render = fn(&VerovioToolkit) -> anyhow::Result<String>;
+
+static CAPABILITIES: OnceLock<Capabilities> = OnceLock::new();
+
+impl VerovioToolkit {
+    /// Probe which formats and subsystems the linked library supports.
+    ///
+    /// The probe loads a tiny document per format into a scratch toolkit, so it
+    /// does not disturb this one, and may log verovio errors for the formats that
+    /// are missing. The result is computed once per process.
+    pub fn capabilities(&self) -> anyhow::Result<Capabilities> {
+        if let Some(capabilities) = CAPABILITIES.get() {
+            return Ok(capabilities.clone());
+        }
+        // The probe only needs what this toolkit has, even if the resources are incomplete.
+        let resource_path = self.get_resource_path()?;
+        let probe = match VerovioToolkit::with_resource(&resource_path) {
+            Ok(probe) => probe,
+            Err(_) => {
+                let probe = VerovioToolkit::without_resource()?;
+                probe.set_resource_path(&resource_path)?;
+                probe
+            }
+        };
+        let capabilities = probe.probe()?;
+        Ok(CAPABILITIES.get_or_init(|| capabilities).clone())
+    }
+
+    fn probe(&self) -> anyhow::Result<Capabilities> {
+        let mut input_formats = vec![];
+        for (format, sample) in SAMPLES {
+            if self.probe_input(format, sample) {
+                input_formats.push(*format);
+            }
+        }
+
+        let mut output_formats = vec![];
+        let mut midi = false;
+        let mut editor = false;
+        if self.probe_input("mei", MEI) {
+            let page_based = self.get_mei(r#"{"scoreBased": false}"#);
+            if page_based.is_ok_and(|mei| self.probe_input("mei-pb-serialized", &mei)) {
+                input_formats.push("mei-pb-serialized");
+            }
+            // Back to the score-based document for the outputs.
+            self.probe_input("mei", MEI);
+
+            let outputs: [(&str, Render); 8] = [
+                ("svg", |tk| tk.render_to_svg(1, false)),
+                ("mei", |tk| tk.get_mei("")),
+                ("mei-basic", |tk| tk.get_mei(r#"{"basic": true}"#)),
+                ("mei-pb", |tk| tk.get_mei(r#"{"scoreBased": false}"#)),
+                ("midi", |tk| {
+                    get_string(tk.tk_ptr, bindings::vrvShim_renderToMIDI)
+                }),
+                ("timemap", |tk| tk.render_to_timemap(None)),
+                // getHumdrum only has output after a Humdrum import, so convert instead.
+                ("humdrum", |tk| {
+                    get_string2(tk.tk_ptr, MEI, bindings::vrvShim_convertMEIToHumdrum)
+                }),
+                ("pae", |tk| {
+                    get_string(tk.tk_ptr, bindings::vrvShim_renderToPAE)
+                }),
+            ];
+            for (format, render) in outputs {
+                if render(self).is_ok_and(|s| !s.trim().is_empty()) {
+                    output_formats.push(format);
+                }
+            }
+            midi = output_formats.contains(&"midi");
+            editor = get_primitive2(
+                self.tk_ptr,
+                r#"{"action":"commit"}"#,
+                bindings::vrvShim_edit,
+            )
+            .unwrap_or(false);
+        }
+
+        Ok(Capabilities {
+            humdrum: input_formats.contains(&"humdrum"),
+            pae: input_formats.contains(&"pae"),
+            abc: input_formats.contains(&"abc"),
+            input_formats,
+            output_formats,
+            editor,
+            midi,
+        })
+    }
+
+    fn probe_input(&self, format: &str, sample: &str) -> bool {
+        let load = || -> anyhow::Result<bool> {
+            Ok(
+                get_primitive2(self.tk_ptr, format, bindings::vrvShim_setInputFrom)?
+                    && get_primitive2(self.tk_ptr, sample, bindings::vrvShim_loadData)?
+                    && get_primitive::<i32>(self.tk_ptr, bindings::vrvShim_getPageCount)? > 0,
+            )
+        };
+        load().unwrap_or(false)
+    }
+}
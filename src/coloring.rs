@@ -0,0 +1,191 @@
+//! Colouring staves, layers or parts, for SVG, PNG and PDF output.
+
+use crate::svg::{attributes, classes, rewrite_with, set, write_tag};
+use crate::{AllOptions, VerovioToolkit};
+use std::collections::HashMap;
+
+/// Colours distinct enough to tell voices apart, also when printed.
+const DEFAULT_PALETTE: [&str; 8] = [
+    "#1f77b4", "#d62728", "#2ca02c", "#ff7f0e", "#9467bd", "#8c564b", "#e377c2", "#17becf",
+];
+
+/// What gets a colour of its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorBy {
+    /// Every staff, by its number.
+    Staff,
+    /// Every layer, by its number, so the voices of a staff differ.
+    Layer,
+    /// Every part, the staves sharing a label, such as the two unlabelled staves of a
+    /// labelled piano group, sharing a colour.
+    Part,
+}
+
+/// A colouring preset, applied by [`VerovioToolkit::render_to_svg_colored`] and
+/// carried into the PNG and PDF outputs through their options.
+#[derive(Debug, Clone)]
+pub struct Coloring {
+    pub by: ColorBy,
+    /// CSS colours, used in turn and from the start again when there are more
+    /// staves, layers or parts than colours.
+    pub palette: Vec<String>,
+}
+
+impl Coloring {
+    /// Colour with the default palette.
+    pub fn new(by: ColorBy) -> Coloring {
+        Coloring {
+            by,
+            palette: DEFAULT_PALETTE.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+}
+
+impl VerovioToolkit {
+    /// Render a page to SVG with the staves, layers or parts in the colours of `coloring`.
+    ///
+    /// The page is rendered with the `n` attribute of staves and layers added through
+    /// `svg_additional_attribute`, the other options are left as they are.
+    pub fn render_to_svg_colored(
+        &self,
+        page_no: i32,
+        coloring: &Coloring,
+    ) -> anyhow::Result<String> {
+        let mut additional = self
+            .get_options()?
+            .svg_additional_attribute
+            .unwrap_or_default();
+        for attribute in ["staff@n", "layer@n"] {
+            if !additional.iter().any(|a| a == attribute) {
+                additional.push(attribute.to_string());
+            }
+        }
+        let options = AllOptions {
+            svg_additional_attribute: Some(additional),
+            ..Default::default()
+        };
+        let svg = self.with_options(&options, |tk| tk.render_to_svg(page_no, false))?;
+
+        let class = match coloring.by {
+            ColorBy::Layer => "layer",
+            ColorBy::Staff | ColorBy::Part => "staff",
+        };
+        let parts = match coloring.by {
+            ColorBy::Part => self.parts()?,
+            ColorBy::Staff | ColorBy::Layer => HashMap::new(),
+        };
+        let color = |n: &str| {
+            let index = match coloring.by {
+                ColorBy::Part => *parts.get(n)?,
+                ColorBy::Staff | ColorBy::Layer => n.parse::<usize>().ok()?.checked_sub(1)?,
+            };
+            coloring.palette.get(index % coloring.palette.len().max(1))
+        };
+        Ok(color_elements(&svg, class, color))
+    }
+
+    /// Render a page to SVG for the PNG and PDF outputs, coloured if asked to.
+    #[cfg(any(feature = "raster", feature = "pdf"))]
+    pub(crate) fn render_to_svg_for_output(
+        &self,
+        page_no: i32,
+        coloring: Option<&Coloring>,
+    ) -> anyhow::Result<String> {
+        match coloring {
+            Some(coloring) => self.render_to_svg_colored(page_no, coloring),
+            None => self.render_to_svg(page_no, false),
+        }
+    }
+
+    /// The index of the part of every staff number, parts being told apart by the label
+    /// of the staff or, for an unlabelled staff, of its innermost labelled staff group,
+    /// in score order.
+    fn parts(&self) -> anyhow::Result<HashMap<String, usize>> {
+        let mei = self.get_mei("")?;
+        let doc = roxmltree::Document::parse(&mei)?;
+        let label = |node: roxmltree::Node| {
+            node.attribute("label").map(String::from).or_else(|| {
+                node.children()
+                    .find(|n| n.has_tag_name("label"))
+                    .and_then(|n| n.text())
+                    .map(|t| t.trim().to_string())
+            })
+        };
+        let mut labels: Vec<String> = vec![];
+        let mut parts = HashMap::new();
+        let Some(score_def) = doc.descendants().find(|n| n.has_tag_name("scoreDef")) else {
+            return Ok(parts);
+        };
+        for staff_def in score_def
+            .descendants()
+            .filter(|n| n.has_tag_name("staffDef"))
+        {
+            let Some(n) = staff_def.attribute("n") else {
+                continue;
+            };
+            let part = label(staff_def)
+                .or_else(|| {
+                    staff_def
+                        .ancestors()
+                        .skip(1)
+                        .take_while(|a| !a.has_tag_name("scoreDef"))
+                        .filter(|a| a.has_tag_name("staffGrp"))
+                        .find_map(label)
+                })
+                .unwrap_or_else(|| format!("staff {}", n));
+            let index = match labels.iter().position(|l| *l == part) {
+                Some(index) => index,
+                None => {
+                    labels.push(part);
+                    labels.len() - 1
+                }
+            };
+            parts.insert(n.to_string(), index);
+        }
+        Ok(parts)
+    }
+}
+
+/// Colour the elements of `svg` with the class `class` by their `data-n` attribute.
+fn color_elements<'a>(
+    svg: &str,
+    class: &str,
+    color: impl Fn(&str) -> Option<&'a String>,
+) -> String {
+    rewrite_with(
+        svg,
+        |tag, out| {
+            let mut attributes = attributes(tag);
+            let matches =
+                classes(&attributes).is_some_and(|c| c.split_whitespace().any(|c| c == class));
+            let n = attributes
+                .iter()
+                .find(|(n, _)| n == "data-n")
+                .map(|(_, v)| v.clone());
+            match n.filter(|_| matches).and_then(|n| color(&n)) {
+                Some(color) => {
+                    set(&mut attributes, "fill", color.clone());
+                    set(&mut attributes, "color", color.clone());
+                    write_tag(tag, &attributes, out);
+                }
+                None => *out += tag,
+            }
+        },
+        |css, out| *out += css,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colors_by_class_and_data_class() {
+        let palette = ["red".to_string(), "blue".to_string()];
+        let svg = r#"<svg><g class="staff" data-n="1"/><g data-class="staff" data-n="2"/><g class="layer" data-n="1"/><g class="staff"/></svg>"#;
+        assert_eq!(
+            color_elements(svg, "staff", |n| palette.get(n.parse::<usize>().ok()? - 1)),
+            r#"<svg><g class="staff" data-n="1" fill="red" color="red"/><g data-class="staff" data-n="2" fill="blue" color="blue"/><g class="layer" data-n="1"/><g class="staff"/></svg>"#
+        );
+    }
+}
@@ -0,0 +1,80 @@
+//! Rendering a range of measures on pages of their own, such as for worksheets.
+
+use crate::{AllOptions, VerovioToolkit};
+use std::ops::RangeInclusive;
+
+impl VerovioToolkit {
+    /// Render the measures of a range to SVG, laid out with `options`, one SVG per page.
+    ///
+    /// Measures are counted from 1 in score order, so ranges starting at 0 or ending
+    /// before they start are an error. The selection and options in place before are
+    /// restored and the document is laid out again afterwards.
+    pub fn render_excerpt(
+        &self,
+        measures: RangeInclusive<u32>,
+        options: &AllOptions,
+    ) -> anyhow::Result<Vec<String>> {
+        let selection = serde_json::json!({ "measureRange": measure_range(&measures)? });
+        self.render_selection(&selection.to_string(), options)
+    }
+
+    /// Render the measures from the one with id `start` to the one with id `end`,
+    /// as [`VerovioToolkit::render_excerpt`].
+    pub fn render_excerpt_between(
+        &self,
+        start: &str,
+        end: &str,
+        options: &AllOptions,
+    ) -> anyhow::Result<Vec<String>> {
+        let selection = serde_json::json!({ "start": start, "end": end });
+        self.render_selection(&selection.to_string(), options)
+    }
+
+    pub(crate) fn render_selection(
+        &self,
+        selection: &str,
+        options: &AllOptions,
+    ) -> anyhow::Result<Vec<String>> {
+        let previous = self.selection.borrow().clone();
+        let result = self.with_options(options, |tk| {
+            if !tk.select(selection)? {
+                return Err(anyhow::anyhow!(
+                    "verovio rejected the selection {}",
+                    selection
+                ));
+            }
+            tk.redo_layout("")?;
+            tk.pages()?
+                .map(|page| page.map(|page| page.svg))
+                .collect::<anyhow::Result<Vec<_>>>()
+        });
+        self.select(previous.as_deref().unwrap_or("{}"))?;
+        self.redo_layout("")?;
+        result
+    }
+}
+
+/// The `measureRange` of a selection.
+fn measure_range(measures: &RangeInclusive<u32>) -> anyhow::Result<String> {
+    if *measures.start() == 0 || measures.is_empty() {
+        return Err(anyhow::anyhow!(
+            "the measures {}..={} are not a range of measures counted from 1",
+            measures.start(),
+            measures.end()
+        ));
+    }
+    Ok(format!("{}-{}", measures.start(), measures.end()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measure_ranges() {
+        assert_eq!(measure_range(&(1..=1)).unwrap(), "1-1");
+        assert_eq!(measure_range(&(3..=8)).unwrap(), "3-8");
+        assert!(measure_range(&(0..=4)).is_err());
+        assert!(measure_range(&RangeInclusive::new(5, 4)).is_err());
+    }
+}
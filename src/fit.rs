@@ -0,0 +1,131 @@
+//! Choosing the scale that fits the pages to a viewport.
+
+use crate::{AllOptions, VerovioToolkit};
+use anyhow::anyhow;
+use serde::Serialize;
+
+/// The scales verovio accepts, in percent.
+const SCALES: std::ops::RangeInclusive<f64> = 1.0..=1000.0;
+
+/// The outcome of [`VerovioToolkit::fit_to_width`] and [`VerovioToolkit::fit_to_page`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Fit {
+    /// The scale set, in percent.
+    pub scale: i32,
+    /// The page count after laying the document out again.
+    pub page_count: i32,
+    /// The distance between two staff lines at that scale, in pixels, two `unit`s.
+    pub staff_space_px: f64,
+}
+
+impl VerovioToolkit {
+    /// Set the largest scale at which a page is at most `width_px` wide.
+    ///
+    /// The width of a page is the `page_width` option, or `page_height` with `landscape`.
+    /// Verovio draws the whole page with its margins, so the margins do not change the
+    /// scale, only how much of the page the music takes. Nor does `unit`, which sizes the
+    /// staves within the page and is only reflected in [`Fit::staff_space_px`]. With
+    /// `adjust_page_width` pages can be narrower than that.
+    ///
+    /// With the default 2100 wide page and 50 wide margins, `fit_to_width(1050)` sets the
+    /// scale to 50: the page is drawn 1050 pixels wide, its music 1000 of them, and with
+    /// the default `unit` of 9 the staff lines are 9 pixels apart.
+    pub fn fit_to_width(&self, width_px: u32) -> anyhow::Result<Fit> {
+        self.fit(scale_for(&self.get_options()?, width_px, None)?)
+    }
+
+    /// Set the largest scale at which a page fits in `width_px` by `height_px`.
+    ///
+    /// As for [`VerovioToolkit::fit_to_width`], the page size is that of the `page_width` and
+    /// `page_height` options, and neither the margins nor `unit` change the scale. With
+    /// `adjust_page_height` pages can be shorter.
+    pub fn fit_to_page(&self, width_px: u32, height_px: u32) -> anyhow::Result<Fit> {
+        self.fit(scale_for(&self.get_options()?, width_px, Some(height_px))?)
+    }
+
+    /// Set `scale`, laying the document out again only if it changed.
+    fn fit(&self, scale: i32) -> anyhow::Result<Fit> {
+        if scale != self.get_scale()? {
+            if !self.set_scale(scale)? {
+                return Err(anyhow!("verovio rejected the scale {}", scale));
+            }
+            self.redo_layout("")?;
+        }
+        let unit = self.get_options()?.unit.unwrap_or(9.0);
+        Ok(Fit {
+            scale,
+            page_count: self.get_page_count()?,
+            staff_space_px: staff_space_px(unit, scale),
+        })
+    }
+}
+
+/// The page size in verovio units. The margins are part of it and only checked to
+/// leave room for the score.
+fn page_size(options: &AllOptions) -> anyhow::Result<(f64, f64)> {
+    let mut width = options.page_width.unwrap_or(2100) as f64;
+    let mut height = options.page_height.unwrap_or(2970) as f64;
+    if options.landscape.unwrap_or(false) {
+        std::mem::swap(&mut width, &mut height);
+    }
+    let margins = |a: Option<i32>, b: Option<i32>| (a.unwrap_or(50) + b.unwrap_or(50)) as f64;
+    if width <= margins(options.page_margin_left, options.page_margin_right)
+        || height <= margins(options.page_margin_top, options.page_margin_bottom)
+    {
+        return Err(anyhow!("the page margins leave no room for the score"));
+    }
+    Ok((width, height))
+}
+
+/// The largest scale verovio accepts at which a page fits in `width_px` by `height_px`.
+fn scale_for(options: &AllOptions, width_px: u32, height_px: Option<u32>) -> anyhow::Result<i32> {
+    let (page_width, page_height) = page_size(options)?;
+    let mut scale = width_px as f64 * 100.0 / page_width;
+    if let Some(height_px) = height_px {
+        scale = scale.min(height_px as f64 * 100.0 / page_height);
+    }
+    Ok(scale.floor().clamp(*SCALES.start(), *SCALES.end()) as i32)
+}
+
+fn staff_space_px(unit: f64, scale: i32) -> f64 {
+    2.0 * unit * scale as f64 / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scales() {
+        let defaults = AllOptions::default();
+        assert_eq!(scale_for(&defaults, 1050, None).unwrap(), 50);
+        assert_eq!(scale_for(&defaults, 1070, None).unwrap(), 50);
+        assert_eq!(scale_for(&defaults, 10, None).unwrap(), 1);
+        assert_eq!(scale_for(&defaults, 100_000, None).unwrap(), 1000);
+        assert_eq!(scale_for(&defaults, 1050, Some(2970)).unwrap(), 50);
+        assert_eq!(scale_for(&defaults, 1050, Some(297)).unwrap(), 10);
+        assert_eq!(staff_space_px(9.0, 50), 9.0);
+
+        // The margins leave the scale alone.
+        let margins = AllOptions {
+            page_margin_left: Some(500),
+            page_margin_right: Some(500),
+            ..Default::default()
+        };
+        assert_eq!(scale_for(&margins, 1050, None).unwrap(), 50);
+        let landscape = AllOptions {
+            landscape: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(page_size(&landscape).unwrap(), (2970.0, 2100.0));
+        assert_eq!(scale_for(&landscape, 1485, None).unwrap(), 50);
+
+        let no_room = AllOptions {
+            page_width: Some(200),
+            page_margin_left: Some(100),
+            page_margin_right: Some(100),
+            ..Default::default()
+        };
+        assert!(scale_for(&no_room, 1050, None).is_err());
+    }
+}
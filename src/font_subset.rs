@@ -0,0 +1,154 @@
+//! Subsetting of TrueType fonts to the glyphs a PDF uses.
+
+use crate::woff2::{checksum, sfnt};
+use anyhow::{anyhow, bail};
+use std::collections::BTreeSet;
+use ttf_parser::{RawFace, Tag};
+
+/// Tables a PDF viewer needs to draw the glyphs of an embedded TrueType font, besides
+/// glyf, loca and head, which are rewritten.
+const COPIED_TABLES: [&[u8; 4]; 6] = [b"cvt ", b"fpgm", b"hhea", b"hmtx", b"maxp", b"prep"];
+
+/// A TrueType font with only the tables needed to draw `glyphs`, and only their
+/// outlines. Glyph ids are kept, the outlines of the other glyphs left empty.
+pub(crate) fn subset(
+    data: &[u8],
+    index: u32,
+    glyphs: impl IntoIterator<Item = u16>,
+) -> anyhow::Result<Vec<u8>> {
+    let face = RawFace::parse(data, index)?;
+    let table = |tag: &[u8; 4]| face.table(Tag::from_bytes(tag));
+    let required = |tag: &[u8; 4]| {
+        table(tag).ok_or_else(|| anyhow!("font has no {} table", String::from_utf8_lossy(tag)))
+    };
+    let head = required(b"head")?;
+    let glyf = required(b"glyf")?;
+    let loca = required(b"loca")?;
+    let num_glyphs = u16_at(required(b"maxp")?, 4)? as usize;
+    let long_offsets = u16_at(head, 50)? != 0;
+
+    let outline = |glyph: u16| -> anyhow::Result<&[u8]> {
+        let offset = |i: usize| -> anyhow::Result<usize> {
+            Ok(if long_offsets {
+                u32_at(loca, i * 4)? as usize
+            } else {
+                u16_at(loca, i * 2)? as usize * 2
+            })
+        };
+        let glyph = glyph as usize;
+        let (start, end) = (offset(glyph)?, offset(glyph + 1)?);
+        glyf.get(start..end.max(start))
+            .ok_or_else(|| anyhow!("glyph {} is outside the glyf table", glyph))
+    };
+
+    // The glyphs used, .notdef and the components of the composite glyphs among them.
+    let mut kept = BTreeSet::new();
+    let mut pending = vec![0];
+    pending.extend(glyphs);
+    while let Some(glyph) = pending.pop() {
+        if (glyph as usize) < num_glyphs && kept.insert(glyph) {
+            pending.extend(components(outline(glyph)?)?);
+        }
+    }
+
+    let mut new_glyf = vec![];
+    let mut new_loca = vec![];
+    for glyph in 0..num_glyphs as u16 {
+        new_loca.extend((new_glyf.len() as u32).to_be_bytes());
+        if kept.contains(&glyph) {
+            new_glyf.extend(outline(glyph)?);
+            new_glyf.resize(new_glyf.len().next_multiple_of(4), 0);
+        }
+    }
+    new_loca.extend((new_glyf.len() as u32).to_be_bytes());
+
+    let mut new_head = head.to_vec();
+    if new_head.len() < 54 {
+        bail!("font has a truncated head table");
+    }
+    // No checksum adjustment yet, and long loca offsets.
+    new_head[8..12].fill(0);
+    new_head[50..52].copy_from_slice(&1u16.to_be_bytes());
+
+    let mut tables = vec![
+        (*b"glyf", new_glyf),
+        (*b"loca", new_loca),
+        (*b"head", new_head),
+    ];
+    for tag in COPIED_TABLES {
+        if let Some(data) = table(tag) {
+            tables.push((*tag, data.to_vec()));
+        }
+    }
+    let mut font = sfnt(0x0001_0000, tables);
+    let num_tables = u16::from_be_bytes([font[4], font[5]]) as usize;
+    let head_offset = font[12..12 + 16 * num_tables]
+        .chunks_exact(16)
+        .find(|record| &record[..4] == b"head")
+        .map(|record| u32::from_be_bytes([record[8], record[9], record[10], record[11]]))
+        .ok_or_else(|| anyhow!("subset font has no head table"))? as usize;
+    let adjustment = 0xb1b0_afbau32.wrapping_sub(checksum(&font));
+    font[head_offset + 8..head_offset + 12].copy_from_slice(&adjustment.to_be_bytes());
+    Ok(font)
+}
+
+/// The glyphs a composite glyph is made of, none for a simple glyph.
+fn components(outline: &[u8]) -> anyhow::Result<Vec<u16>> {
+    const ARGS_ARE_WORDS: u16 = 0x0001;
+    const HAVE_SCALE: u16 = 0x0008;
+    const MORE_COMPONENTS: u16 = 0x0020;
+    const HAVE_XY_SCALE: u16 = 0x0040;
+    const HAVE_TWO_BY_TWO: u16 = 0x0080;
+
+    if outline.is_empty() || u16_at(outline, 0)? as i16 >= 0 {
+        return Ok(vec![]);
+    }
+    let mut components = vec![];
+    let mut offset = 10;
+    loop {
+        let flags = u16_at(outline, offset)?;
+        components.push(u16_at(outline, offset + 2)?);
+        offset += 4 + if flags & ARGS_ARE_WORDS != 0 { 4 } else { 2 };
+        if flags & HAVE_SCALE != 0 {
+            offset += 2;
+        } else if flags & HAVE_XY_SCALE != 0 {
+            offset += 4;
+        } else if flags & HAVE_TWO_BY_TWO != 0 {
+            offset += 8;
+        }
+        if flags & MORE_COMPONENTS == 0 {
+            return Ok(components);
+        }
+    }
+}
+
+fn u16_at(data: &[u8], offset: usize) -> anyhow::Result<u16> {
+    data.get(offset..offset + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .ok_or_else(|| anyhow!("font table is truncated"))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> anyhow::Result<u32> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| anyhow!("font table is truncated"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf_text::tests::font;
+
+    #[test]
+    fn keeps_the_outlines_of_the_glyphs_used() {
+        let subset = subset(&font(), 0, [2, 1000]).unwrap();
+        assert_eq!(checksum(&subset), 0xb1b0_afba);
+        let face = ttf_parser::Face::parse(&subset, 0).unwrap();
+        assert_eq!(face.number_of_glyphs(), 54);
+        for (glyph, kept) in [(0, true), (2, true), (3, false), (53, false)] {
+            let bbox = face.glyph_bounding_box(ttf_parser::GlyphId(glyph));
+            assert_eq!(bbox.is_some(), kept, "glyph {}", glyph);
+        }
+        assert_eq!(face.glyph_hor_advance(ttf_parser::GlyphId(53)), Some(500));
+    }
+}
@@ -0,0 +1,377 @@
+//! SMuFL fonts registered from memory.
+
+use crate::svg::escape;
+use crate::{AllOptions, VerovioToolkit};
+use anyhow::anyhow;
+use serde::Deserialize;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::fs;
+use std::io::{Cursor, Read, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use zip::write::SimpleFileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+/// Glyphs the font does not provide although verovio's default font does.
+#[derive(Debug, Clone)]
+pub struct MissingGlyphs {
+    pub font: String,
+    /// SMuFL code points such as `E050`.
+    pub glyphs: Vec<String>,
+}
+
+impl fmt::Display for MissingGlyphs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "font {} is missing {} glyphs: {}",
+            self.font,
+            self.glyphs.len(),
+            self.glyphs.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for MissingGlyphs {}
+
+/// The font verovio uses by default, whose glyphs every font must cover.
+const DEFAULT_FONT: &str = "Leipzig";
+/// The font verovio falls back to, which has names for every glyph.
+const FALLBACK_FONT: &str = "Bravura";
+/// Font units per staff space in the generated font files.
+const UNITS_PER_STAFF_SPACE: f64 = 250.0;
+
+static NEXT_FONT_DIR: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Deserialize)]
+struct Metadata {
+    #[serde(rename = "glyphBBoxes", default)]
+    glyph_bboxes: HashMap<String, BBox>,
+    #[serde(rename = "glyphAdvanceWidths", default)]
+    glyph_advance_widths: HashMap<String, f64>,
+    #[serde(rename = "glyphsWithAnchors", default)]
+    glyphs_with_anchors: HashMap<String, HashMap<String, [f64; 2]>>,
+}
+
+#[derive(Deserialize)]
+struct BBox {
+    #[serde(rename = "bBoxNE")]
+    ne: [f64; 2],
+    #[serde(rename = "bBoxSW")]
+    sw: [f64; 2],
+}
+
+impl VerovioToolkit {
+    /// Register a SMuFL font from a zip in the layout `fontAddCustom` expects:
+    /// `<name>.xml` with the glyph bounding boxes, and one `<name>/<code>.xml` per glyph.
+    ///
+    /// The font must cover the glyphs of verovio's default font.
+    /// It can be selected afterwards with the `font` option.
+    ///
+    /// Font names are made of ASCII letters, digits, `-` and `_`.
+    pub fn add_font(&self, name: &str, bytes: impl AsRef<[u8]>) -> anyhow::Result<()> {
+        check_font_name(name)?;
+        let bytes = bytes.as_ref();
+        let provided = zip_glyphs(name, bytes)?;
+        self.check_coverage(name, &provided)?;
+        self.register_font(name, bytes)
+    }
+
+    /// Register a SMuFL font from its metadata JSON and one SVG per glyph.
+    ///
+    /// Glyphs are keyed by their code point, such as `E050`. Their paths must be in
+    /// font units with the y axis pointing up, at 1000 units per em, as glyph outlines
+    /// are exported from a font. Glyph names in the metadata are resolved with the
+    /// names of verovio's fallback font.
+    pub fn add_font_from_glyphs<K, V>(
+        &self,
+        name: &str,
+        metadata: impl AsRef<[u8]>,
+        glyphs: impl IntoIterator<Item = (K, V)>,
+    ) -> anyhow::Result<()>
+    where
+        K: AsRef<str>,
+        V: AsRef<[u8]>,
+    {
+        check_font_name(name)?;
+        let metadata: Metadata = serde_json::from_slice(metadata.as_ref())?;
+        let resource_path = PathBuf::from(self.get_resource_path()?);
+        let fallback = fs::read_to_string(resource_path.join(format!("{}.xml", FALLBACK_FONT)))?;
+        let codes = glyph_names(&fallback)?;
+
+        let mut paths = HashMap::new();
+        for (code, svg) in glyphs {
+            let svg = std::str::from_utf8(svg.as_ref())?;
+            let doc = roxmltree::Document::parse(svg)?;
+            let d: Vec<&str> = doc
+                .descendants()
+                .filter(|n| n.has_tag_name("path"))
+                .filter_map(|n| n.attribute("d"))
+                .collect();
+            paths.insert(code.as_ref().to_uppercase(), d.join(" "));
+        }
+
+        let mut xml = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<bounding-boxes font-family=\"{}\" units-per-em=\"1000\">\n",
+            name
+        );
+        let mut provided = BTreeSet::new();
+        let mut zip = ZipWriter::new(Cursor::new(vec![]));
+        let options = SimpleFileOptions::default();
+        let mut glyph_names: Vec<&String> = metadata.glyph_bboxes.keys().collect();
+        glyph_names.sort();
+        for glyph in glyph_names {
+            let Some(code) = codes.get(glyph.as_str()) else {
+                continue;
+            };
+            let Some(d) = paths.get(code) else {
+                continue;
+            };
+            let bbox = &metadata.glyph_bboxes[glyph];
+            let x = bbox.sw[0] * UNITS_PER_STAFF_SPACE;
+            let y = bbox.sw[1] * UNITS_PER_STAFF_SPACE;
+            let w = (bbox.ne[0] - bbox.sw[0]) * UNITS_PER_STAFF_SPACE;
+            let h = (bbox.ne[1] - bbox.sw[1]) * UNITS_PER_STAFF_SPACE;
+            let advance = metadata
+                .glyph_advance_widths
+                .get(glyph)
+                .map(|a| a * UNITS_PER_STAFF_SPACE)
+                .unwrap_or(w);
+            xml += &format!(
+                "  <g c=\"{}\" x=\"{:.1}\" y=\"{:.1}\" w=\"{:.1}\" h=\"{:.1}\" h-a-x=\"{:.0}\" n=\"{}\"",
+                code, x, y, w, h, advance, glyph
+            );
+            match metadata.glyphs_with_anchors.get(glyph) {
+                Some(anchors) => {
+                    xml += ">";
+                    let mut anchors: Vec<_> = anchors.iter().collect();
+                    anchors.sort_by_key(|(n, _)| n.as_str());
+                    for (anchor, [ax, ay]) in anchors {
+                        xml += &format!("<a n=\"{}\" x=\"{}\" y=\"{}\"/>", escape(anchor), ax, ay);
+                    }
+                    xml += "</g>\n";
+                }
+                None => xml += "/>\n",
+            }
+
+            zip.start_file(format!("{}/{}.xml", name, code), options)?;
+            write!(
+                zip,
+                "<symbol id=\"{}\" viewBox=\"0 0 1000 1000\" overflow=\"inherit\"><path transform=\"scale(1,-1)\" d=\"{}\"/></symbol>",
+                code,
+                escape(d)
+            )?;
+            provided.insert(code.clone());
+        }
+        xml += "</bounding-boxes>\n";
+        zip.start_file(format!("{}.xml", name), options)?;
+        zip.write_all(xml.as_bytes())?;
+        let bytes = zip.finish()?.into_inner();
+
+        self.check_coverage(name, &provided)?;
+        self.register_font(name, &bytes)
+    }
+
+    /// List the SMuFL fonts available to this toolkit,
+    /// those in the resource path followed by the ones added with [`VerovioToolkit::add_font`].
+    pub fn list_fonts(&self) -> anyhow::Result<Vec<String>> {
+        let resource_path = PathBuf::from(self.get_resource_path()?);
+        let mut fonts = vec![];
+        for entry in fs::read_dir(&resource_path)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|e| e == "xml")
+                && path.with_extension("").is_dir()
+                && let Some(name) = path.file_stem().and_then(|s| s.to_str())
+            {
+                fonts.push(name.to_string());
+            }
+        }
+        fonts.sort();
+        fonts.extend(
+            self.custom_fonts
+                .borrow()
+                .iter()
+                .filter_map(|p| p.file_stem()?.to_str().map(String::from)),
+        );
+        Ok(fonts)
+    }
+
+    fn check_coverage(&self, name: &str, provided: &BTreeSet<String>) -> anyhow::Result<()> {
+        let resource_path = PathBuf::from(self.get_resource_path()?);
+        let default = fs::read_to_string(resource_path.join(format!("{}.xml", DEFAULT_FONT)))?;
+        let missing: Vec<String> = glyph_codes(&default)?
+            .difference(provided)
+            .cloned()
+            .collect();
+        if !missing.is_empty() {
+            return Err(MissingGlyphs {
+                font: name.into(),
+                glyphs: missing,
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Write the font zip where verovio can read it and add it to `fontAddCustom`.
+    fn register_font(&self, name: &str, bytes: &[u8]) -> anyhow::Result<()> {
+        // Verovio takes the font name from the file name, so every font gets its own directory.
+        let dir = std::env::temp_dir().join(format!(
+            "verovior-font-{}-{}",
+            std::process::id(),
+            NEXT_FONT_DIR.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("{}.zip", name));
+        fs::write(&path, bytes)?;
+
+        let registered: Vec<String> = self
+            .custom_fonts
+            .borrow()
+            .iter()
+            .map(|p| p.to_string_lossy().into_owned())
+            .collect();
+        let mut fonts = registered.clone();
+        fonts.push(path.to_string_lossy().into_owned());
+        let options = |fonts| AllOptions {
+            font_add_custom: Some(fonts),
+            ..Default::default()
+        };
+        // A rejected font is not kept, so that it is not passed again with the next one.
+        match self.set_options(&options(fonts)) {
+            Ok(true) => {
+                self.custom_fonts.borrow_mut().push(path);
+                Ok(())
+            }
+            result => {
+                let _ = self.set_options(&options(registered));
+                remove_custom_fonts(&[path]);
+                result?;
+                Err(anyhow!("verovio rejected font {}", name))
+            }
+        }
+    }
+}
+
+/// Remove the font files written by [`VerovioToolkit::add_font`].
+pub(crate) fn remove_custom_fonts(fonts: &[PathBuf]) {
+    for font in fonts {
+        if let Some(dir) = font.parent() {
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+}
+
+/// Fail unless `name` can be used as a file name and in XML attributes as it is.
+fn check_font_name(name: &str) -> anyhow::Result<()> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(anyhow!(
+            "invalid font name {:?}, only ASCII letters, digits, - and _ are allowed",
+            name
+        ));
+    }
+    Ok(())
+}
+
+/// Code points of the glyphs of a font zip that are both listed in `<name>.xml`
+/// and drawn in `<name>/<code>.xml`.
+fn zip_glyphs(name: &str, bytes: &[u8]) -> anyhow::Result<BTreeSet<String>> {
+    let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+    let mut metadata = String::new();
+    archive
+        .by_name(&format!("{}.xml", name))
+        .map_err(|_| anyhow!("font zip has no {}.xml", name))?
+        .read_to_string(&mut metadata)?;
+    let drawn: BTreeSet<String> = archive
+        .file_names()
+        .filter_map(|file| {
+            file.strip_prefix(name)?
+                .strip_prefix('/')?
+                .strip_suffix(".xml")
+        })
+        .map(str::to_uppercase)
+        .collect();
+    let mut provided = glyph_codes(&metadata)?;
+    provided.retain(|code| drawn.contains(code));
+    Ok(provided)
+}
+
+/// Code points of the glyphs listed in a verovio font file.
+fn glyph_codes(xml: &str) -> anyhow::Result<BTreeSet<String>> {
+    let doc = roxmltree::Document::parse(xml)?;
+    Ok(doc
+        .descendants()
+        .filter(|n| n.has_tag_name("g"))
+        .filter_map(|n| n.attribute("c"))
+        .map(str::to_uppercase)
+        .collect())
+}
+
+/// Glyph names to code points, from a verovio font file.
+fn glyph_names(xml: &str) -> anyhow::Result<HashMap<String, String>> {
+    let doc = roxmltree::Document::parse(xml)?;
+    Ok(doc
+        .descendants()
+        .filter(|n| n.has_tag_name("g"))
+        .filter_map(|n| Some((n.attribute("n")?.into(), n.attribute("c")?.to_uppercase())))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A font zip listing `listed` in `<name>.xml`, with the `drawn` glyphs in `<dir>/`.
+    fn font_zip(name: &str, listed: &[&str], dir: &str, drawn: &[&str]) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(vec![]));
+        let options = SimpleFileOptions::default();
+        zip.start_file(format!("{}.xml", name), options).unwrap();
+        let mut xml = String::from("<bounding-boxes>");
+        for code in listed {
+            xml += &format!("<g c=\"{}\"/>", code);
+        }
+        xml += "</bounding-boxes>";
+        zip.write_all(xml.as_bytes()).unwrap();
+        for code in drawn {
+            zip.start_file(format!("{}/{}.xml", dir, code), options)
+                .unwrap();
+            zip.write_all(b"<symbol/>").unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn font_names() {
+        assert!(check_font_name("My_Font-2").is_ok());
+        for name in ["", "../Leipzig", "a/b", "a.b", "a\"b", "a b", "Läipzig"] {
+            assert!(check_font_name(name).is_err(), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn glyphs_need_their_files() {
+        let zip = font_zip("Test", &["E050", "e062", "E0A4"], "Test", &["E050", "E062"]);
+        let provided = zip_glyphs("Test", &zip).unwrap();
+        assert_eq!(provided.into_iter().collect::<Vec<_>>(), ["E050", "E062"]);
+    }
+
+    #[test]
+    fn glyph_files_of_other_fonts_do_not_count() {
+        let zip = font_zip("Test", &["E050"], "Test2", &["E050"]);
+        assert!(zip_glyphs("Test", &zip).unwrap().is_empty());
+        let zip = font_zip("Test", &["E050"], "", &["Test/E050"]);
+        assert!(zip_glyphs("Test", &zip).unwrap().is_empty());
+    }
+
+    #[test]
+    fn metadata_is_required() {
+        let zip = font_zip("Test", &["E050"], "Test", &["E050"]);
+        assert!(zip_glyphs("Other", &zip).is_err());
+        assert!(zip_glyphs("Test", b"not a zip").is_err());
+    }
+}
@@ -0,0 +1,348 @@
+//! Element positions read from the bounding boxes verovio adds to the SVG.
+
+use crate::{AllOptions, VerovioToolkit};
+use anyhow::anyhow;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// A rectangle, `y` growing downwards as in SVG.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Rect {
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.x && x <= self.x + self.width && y >= self.y && y <= self.y + self.height
+    }
+}
+
+/// Where an element is drawn on a page.
+#[derive(Debug, Clone, Serialize)]
+pub struct ElementGeometry {
+    /// The element type, such as `note`, `rest`, `measure`, `staff` or `system`.
+    pub kind: String,
+    /// Bounding box in page units.
+    pub rect: Rect,
+    /// Id of the closest enclosing element that has a bounding box.
+    pub parent: Option<String>,
+}
+
+/// The bounding boxes of the elements of a page, returned by [`VerovioToolkit::page_geometry`].
+#[derive(Debug, Clone, Serialize)]
+pub struct PageGeometry {
+    /// Size of the SVG in pixels, or of its viewBox with `svg_view_box`.
+    pub width: f64,
+    pub height: f64,
+    /// The page in page units, which the SVG maps to its size.
+    pub view_box: Rect,
+    /// Elements by id.
+    pub elements: HashMap<String, ElementGeometry>,
+}
+
+impl PageGeometry {
+    /// Convert a point of the SVG, in the units of [`PageGeometry::width`], to page units.
+    pub fn to_page_units(&self, x: f64, y: f64) -> (f64, f64) {
+        (
+            self.view_box.x + x * self.view_box.width / self.width,
+            self.view_box.y + y * self.view_box.height / self.height,
+        )
+    }
+}
+
+impl VerovioToolkit {
+    /// Get the bounding box of every element drawn on a page, in page units.
+    ///
+    /// The page is rendered with `svg_bounding_boxes` enabled, the other options are left as they are.
+    pub fn page_geometry(&self, page_no: i32) -> anyhow::Result<PageGeometry> {
+        let options = AllOptions {
+            svg_bounding_boxes: Some(true),
+            ..Default::default()
+        };
+        let svg = self.with_options(&options, |tk| tk.render_to_svg(page_no, false))?;
+        parse(&svg)
+    }
+}
+
+/// A transform made of translations and scalings, mapping `(x, y)` to `(sx * x + tx, sy * y + ty)`.
+#[derive(Clone, Copy)]
+pub(crate) struct Transform {
+    sx: f64,
+    sy: f64,
+    tx: f64,
+    ty: f64,
+}
+
+impl Transform {
+    const IDENTITY: Transform = Transform {
+        sx: 1.0,
+        sy: 1.0,
+        tx: 0.0,
+        ty: 0.0,
+    };
+
+    /// Apply the `transform` attribute of a child node, which is applied first.
+    fn then(self, attribute: &str) -> Transform {
+        let mut t = self;
+        for function in attribute.split_inclusive(')') {
+            let Some((name, args)) = function.trim().trim_end_matches(')').split_once('(') else {
+                continue;
+            };
+            let args: Vec<f64> = args
+                .split([',', ' '])
+                .filter_map(|a| a.trim().parse().ok())
+                .collect();
+            match (name.trim(), args.as_slice()) {
+                ("translate", [x]) => t.tx += t.sx * x,
+                ("translate", [x, y]) => {
+                    t.tx += t.sx * x;
+                    t.ty += t.sy * y;
+                }
+                ("scale", [s]) => {
+                    t.sx *= s;
+                    t.sy *= s;
+                }
+                ("scale", [x, y]) => {
+                    t.sx *= x;
+                    t.sy *= y;
+                }
+                // Only matrices without rotation or skew, as the other transforms.
+                ("matrix", [a, 0.0, 0.0, d, e, f]) => {
+                    t.tx += t.sx * e;
+                    t.ty += t.sy * f;
+                    t.sx *= a;
+                    t.sy *= d;
+                }
+                _ => {}
+            }
+        }
+        t
+    }
+
+    /// The transform from the coordinates of `node` to page units, made of the
+    /// `transform` attributes of the node and its ancestors within the page.
+    pub(crate) fn of(node: roxmltree::Node) -> Transform {
+        let transforms: Vec<&str> = node
+            .ancestors()
+            .take_while(|n| !(n.has_tag_name("svg") && has_class(*n, "definition-scale")))
+            .filter_map(|n| n.attribute("transform"))
+            .collect();
+        transforms
+            .iter()
+            .rev()
+            .fold(Transform::IDENTITY, |t, attribute| t.then(attribute))
+    }
+
+    pub(crate) fn apply_point(&self, x: f64, y: f64) -> (f64, f64) {
+        (self.sx * x + self.tx, self.sy * y + self.ty)
+    }
+
+    fn apply(&self, rect: Rect) -> Rect {
+        let (x0, x1) = (
+            self.sx * rect.x + self.tx,
+            self.sx * (rect.x + rect.width) + self.tx,
+        );
+        let (y0, y1) = (
+            self.sy * rect.y + self.ty,
+            self.sy * (rect.y + rect.height) + self.ty,
+        );
+        Rect {
+            x: x0.min(x1),
+            y: y0.min(y1),
+            width: (x1 - x0).abs(),
+            height: (y1 - y0).abs(),
+        }
+    }
+}
+
+pub(crate) fn parse(svg: &str) -> anyhow::Result<PageGeometry> {
+    let doc = roxmltree::Document::parse(svg)?;
+    let root = doc.root_element();
+    let length = |name| {
+        root.attribute(name)
+            .map(|v: &str| v.trim_end_matches("px"))
+            .and_then(|v| v.parse::<f64>().ok())
+            .ok_or_else(|| anyhow!("the SVG has no {}", name))
+    };
+    // With `svg_view_box` the size is left to the viewer and only the viewBox is set.
+    let (width, height) = match numbers(root.attribute("viewBox"))[..] {
+        [_, _, w, h] => (w, h),
+        _ => (length("width")?, length("height")?),
+    };
+    let page = root
+        .descendants()
+        .find(|n| n.has_tag_name("svg") && has_class(*n, "definition-scale"))
+        .ok_or_else(|| anyhow!("the SVG has no definition-scale element"))?;
+    let [x, y, w, h] = numbers(page.attribute("viewBox"))[..] else {
+        return Err(anyhow!("the SVG has no page viewBox"));
+    };
+
+    let mut geometry = PageGeometry {
+        width,
+        height,
+        view_box: Rect {
+            x,
+            y,
+            width: w,
+            height: h,
+        },
+        elements: HashMap::new(),
+    };
+    for child in page.children() {
+        collect(child, Transform::IDENTITY, None, &mut geometry.elements);
+    }
+    Ok(geometry)
+}
+
+fn collect(
+    node: roxmltree::Node,
+    transform: Transform,
+    parent: Option<&str>,
+    elements: &mut HashMap<String, ElementGeometry>,
+) {
+    if !node.is_element() {
+        return;
+    }
+    let transform = match node.attribute("transform") {
+        Some(t) => transform.then(t),
+        None => transform,
+    };
+    let id = node.attribute("id").or_else(|| node.attribute("data-id"));
+    let bounding_box = node
+        .children()
+        .find(|n| n.has_tag_name("g") && has_class(*n, "bounding-box"));
+    let mut parent = parent;
+    if let (Some(id), Some(bounding_box)) = (id, bounding_box)
+        && let Some(rect) = bounding_box.children().find(|n| n.has_tag_name("rect"))
+    {
+        let box_transform = match bounding_box.attribute("transform") {
+            Some(t) => transform.then(t),
+            None => transform,
+        };
+        let number = |name| {
+            rect.attribute(name)
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0.0)
+        };
+        let kind = classes(node)
+            .and_then(|c| c.split_whitespace().next())
+            .unwrap_or(node.tag_name().name());
+        elements.insert(
+            id.to_string(),
+            ElementGeometry {
+                kind: kind.to_string(),
+                rect: box_transform.apply(Rect {
+                    x: number("x"),
+                    y: number("y"),
+                    width: number("width"),
+                    height: number("height"),
+                }),
+                parent: parent.map(String::from),
+            },
+        );
+        parent = Some(id);
+    }
+    for child in node.children() {
+        if Some(child) != bounding_box {
+            collect(child, transform, parent, elements);
+        }
+    }
+}
+
+/// The classes of an element, in `data-class` with `svg_html5`.
+fn classes<'a>(node: roxmltree::Node<'a, '_>) -> Option<&'a str> {
+    node.attribute("class")
+        .or_else(|| node.attribute("data-class"))
+}
+
+pub(crate) fn has_class(node: roxmltree::Node, class: &str) -> bool {
+    classes(node).is_some_and(|c| c.split_whitespace().any(|c| c == class))
+}
+
+fn numbers(list: Option<&str>) -> Vec<f64> {
+    list.unwrap_or_default()
+        .split([' ', ','])
+        .filter_map(|v| v.parse().ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn svg(size: &str, page: &str) -> String {
+        format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" {}>
+                <svg class="definition-scale" viewBox="0 0 4000 2000">
+                    <g class="page-margin" transform="translate(100, 50)">{}</g>
+                </svg>
+            </svg>"#,
+            size, page
+        )
+    }
+
+    const PAGE: &str = r#"
+        <g id="m1" class="measure">
+            <g class="bounding-box"><rect x="0" y="0" width="1000" height="500"/></g>
+            <g id="n1" class="note stem-up" transform="matrix(2 0 0 2 10 20)">
+                <g class="bounding-box" transform="translate(5)">
+                    <rect x="1" y="2" width="3" height="4"/>
+                </g>
+            </g>
+        </g>"#;
+
+    #[test]
+    fn transforms() {
+        let t = Transform::IDENTITY.then("translate(100, 50) scale(2)");
+        assert_eq!(t.apply_point(1.0, 1.0), (102.0, 52.0));
+        let t = t.then("matrix(1,0,0,-1,10,20) translate(3 4)");
+        assert_eq!(t.apply_point(0.0, 0.0), (126.0, 82.0));
+        // Rotations are left out.
+        let t = Transform::IDENTITY.then("rotate(90) matrix(0 1 -1 0 5 5)");
+        assert_eq!(t.apply_point(1.0, 2.0), (1.0, 2.0));
+    }
+
+    #[test]
+    fn page_geometry() {
+        let geometry = parse(&svg(r#"width="200px" height="100px""#, PAGE)).unwrap();
+        assert_eq!((geometry.width, geometry.height), (200.0, 100.0));
+        assert_eq!(geometry.to_page_units(100.0, 50.0), (2000.0, 1000.0));
+        let measure = &geometry.elements["m1"];
+        assert_eq!(measure.kind, "measure");
+        assert_eq!(measure.parent, None);
+        assert_eq!(
+            measure.rect,
+            Rect {
+                x: 100.0,
+                y: 50.0,
+                width: 1000.0,
+                height: 500.0
+            }
+        );
+        let note = &geometry.elements["n1"];
+        assert_eq!(note.kind, "note");
+        assert_eq!(note.parent.as_deref(), Some("m1"));
+        assert_eq!(
+            note.rect,
+            Rect {
+                x: 122.0,
+                y: 74.0,
+                width: 6.0,
+                height: 8.0
+            }
+        );
+
+        // `svg_view_box` sets only the viewBox, `svg_html5` moves the ids and classes.
+        let html5 = PAGE
+            .replace(" id=", " data-id=")
+            .replace(" class=\"n", " data-class=\"n");
+        let geometry = parse(&svg(r#"viewBox="0 0 400 200""#, &html5)).unwrap();
+        assert_eq!((geometry.width, geometry.height), (400.0, 200.0));
+        assert_eq!(geometry.to_page_units(100.0, 50.0), (1000.0, 500.0));
+        assert_eq!(geometry.elements["n1"].kind, "note");
+        assert_eq!(geometry.elements["n1"].parent.as_deref(), Some("m1"));
+    }
+}
@@ -0,0 +1,183 @@
+//! Finding the elements drawn at a point of a page.
+
+use crate::{PageGeometry, Rect, VerovioToolkit};
+use serde::Serialize;
+
+/// Cells per side of the grid of an [`ElementIndex`].
+const GRID_SIZE: usize = 64;
+
+/// An element drawn at a point, returned by [`VerovioToolkit::element_at`].
+#[derive(Debug, Clone, Serialize)]
+pub struct ElementHit {
+    pub id: String,
+    /// The element type, such as `note`, `rest` or `measure`.
+    pub kind: String,
+    /// Bounding box in page units.
+    pub rect: Rect,
+}
+
+/// A grid over a page, listing in every cell the elements whose bounding box overlaps it.
+#[derive(Debug, Clone)]
+pub struct ElementIndex {
+    geometry: PageGeometry,
+    /// Hits ordered innermost first, with their nesting depth.
+    hits: Vec<(usize, ElementHit)>,
+    cells: Vec<Vec<usize>>,
+}
+
+impl ElementIndex {
+    pub fn new(geometry: PageGeometry) -> ElementIndex {
+        let mut hits: Vec<(usize, ElementHit)> = geometry
+            .elements
+            .iter()
+            .map(|(id, element)| {
+                let hit = ElementHit {
+                    id: id.clone(),
+                    kind: element.kind.clone(),
+                    rect: element.rect,
+                };
+                (depth(&geometry, id), hit)
+            })
+            .collect();
+        hits.sort_by(|(da, a), (db, b)| {
+            db.cmp(da)
+                .then((a.rect.width * a.rect.height).total_cmp(&(b.rect.width * b.rect.height)))
+                .then_with(|| a.id.cmp(&b.id))
+        });
+
+        let mut index = ElementIndex {
+            geometry,
+            hits: vec![],
+            cells: vec![vec![]; GRID_SIZE * GRID_SIZE],
+        };
+        for (i, (_, hit)) in hits.iter().enumerate() {
+            let (x0, y0) = index.cell(hit.rect.x, hit.rect.y);
+            let (x1, y1) = index.cell(hit.rect.x + hit.rect.width, hit.rect.y + hit.rect.height);
+            for cy in y0..=y1 {
+                for cx in x0..=x1 {
+                    index.cells[cy * GRID_SIZE + cx].push(i);
+                }
+            }
+        }
+        index.hits = hits;
+        index
+    }
+
+    /// The elements at a point of the SVG, innermost first. The point is in the
+    /// units of [`PageGeometry::width`], pixels unless `svg_view_box` is set.
+    pub fn elements_at(&self, x: f64, y: f64) -> Vec<ElementHit> {
+        let (x, y) = self.geometry.to_page_units(x, y);
+        let (cx, cy) = self.cell(x, y);
+        self.cells[cy * GRID_SIZE + cx]
+            .iter()
+            .map(|&i| &self.hits[i].1)
+            .filter(|hit| hit.rect.contains(x, y))
+            .cloned()
+            .collect()
+    }
+
+    pub fn geometry(&self) -> &PageGeometry {
+        &self.geometry
+    }
+
+    /// The cell holding a point in page units, clamped to the grid.
+    fn cell(&self, x: f64, y: f64) -> (usize, usize) {
+        let view_box = &self.geometry.view_box;
+        let clamp = |v: f64| (v.max(0.0) as usize).min(GRID_SIZE - 1);
+        (
+            clamp((x - view_box.x) / view_box.width * GRID_SIZE as f64),
+            clamp((y - view_box.y) / view_box.height * GRID_SIZE as f64),
+        )
+    }
+}
+
+/// How many enclosing elements with a bounding box an element has.
+fn depth(geometry: &PageGeometry, id: &str) -> usize {
+    let mut depth = 0;
+    let mut id = id;
+    while let Some(parent) = geometry.elements.get(id).and_then(|e| e.parent.as_deref()) {
+        depth += 1;
+        id = parent;
+    }
+    depth
+}
+
+impl VerovioToolkit {
+    /// Find the elements drawn at a point of a rendered page, innermost first,
+    /// such as a note, then its chord, layer, staff, measure and system.
+    ///
+    /// The point is in SVG pixels, or in the units of the viewBox with `svg_view_box`.
+    /// To look up several points of the same page, build an [`ElementIndex`]
+    /// from [`VerovioToolkit::page_geometry`] instead.
+    pub fn element_at(&self, page_no: i32, x: f64, y: f64) -> anyhow::Result<Vec<ElementHit>> {
+        Ok(ElementIndex::new(self.page_geometry(page_no)?).elements_at(x, y))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ElementGeometry;
+    use std::collections::HashMap;
+
+    fn rect(x: f64, y: f64, width: f64, height: f64) -> Rect {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// A 640 by 320 pixel page of 6400 by 3200 page units.
+    fn index() -> ElementIndex {
+        let mut elements = HashMap::new();
+        let mut add = |id: &str, kind: &str, rect: Rect, parent: Option<&str>| {
+            let element = ElementGeometry {
+                kind: kind.to_string(),
+                rect,
+                parent: parent.map(String::from),
+            };
+            elements.insert(id.to_string(), element);
+        };
+        add("s1", "system", rect(0.0, 0.0, 6400.0, 3200.0), None);
+        add(
+            "m1",
+            "measure",
+            rect(100.0, 100.0, 3000.0, 1000.0),
+            Some("s1"),
+        );
+        add("b1", "beam", rect(150.0, 150.0, 400.0, 300.0), Some("m1"));
+        add("n1", "note", rect(200.0, 200.0, 100.0, 100.0), Some("m1"));
+        add("t1", "tie", rect(6000.0, -200.0, 1000.0, 400.0), Some("s1"));
+        ElementIndex::new(PageGeometry {
+            width: 640.0,
+            height: 320.0,
+            view_box: rect(0.0, 0.0, 6400.0, 3200.0),
+            elements,
+        })
+    }
+
+    fn ids(hits: Vec<ElementHit>) -> Vec<String> {
+        hits.into_iter().map(|hit| hit.id).collect()
+    }
+
+    #[test]
+    fn innermost_first() {
+        let index = index();
+        // Deeper elements first, then the smaller of the same depth.
+        assert_eq!(ids(index.elements_at(25.0, 25.0)), ["n1", "b1", "m1", "s1"]);
+        assert_eq!(ids(index.elements_at(100.0, 40.0)), ["m1", "s1"]);
+    }
+
+    #[test]
+    fn clamps_at_page_edges() {
+        let index = index();
+        assert_eq!(index.cell(-100.0, 3200.0), (0, GRID_SIZE - 1));
+        assert_eq!(index.cell(7000.0, -200.0), (GRID_SIZE - 1, 0));
+        assert_eq!(ids(index.elements_at(640.0, 320.0)), ["s1"]);
+        // The tie is drawn past the page, where it is still found.
+        assert_eq!(ids(index.elements_at(660.0, -10.0)), ["t1"]);
+        assert!(index.elements_at(-10.0, -10.0).is_empty());
+    }
+}
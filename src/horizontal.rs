@@ -0,0 +1,89 @@
+//! Rendering the whole score on a single system, for views scrolling along with playback.
+
+use crate::{AllOptions, TimemapRenderOptions, VerovioToolkit};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A measure of a [`HorizontalScore`].
+#[derive(Debug, Clone, Serialize)]
+pub struct HorizontalMeasure {
+    pub id: String,
+    /// Where the measure starts and ends, in SVG pixels.
+    pub x_start: f64,
+    pub x_end: f64,
+    /// When the measure starts playing, in milliseconds,
+    /// `None` for a measure the timemap does not list.
+    pub onset_ms: Option<f64>,
+}
+
+/// The score on a single system, returned by [`VerovioToolkit::render_horizontal`].
+#[derive(Debug, Clone, Serialize)]
+pub struct HorizontalScore {
+    pub svg: String,
+    /// Size of the SVG in pixels.
+    pub width: f64,
+    pub height: f64,
+    /// The measures from left to right.
+    pub measures: Vec<HorizontalMeasure>,
+}
+
+#[derive(Deserialize)]
+struct TimemapEntry {
+    tstamp: f64,
+    #[serde(rename = "measureOn")]
+    measure_on: Option<String>,
+}
+
+impl VerovioToolkit {
+    /// Render the loaded document on a single system, with where and when each measure starts.
+    ///
+    /// The document is laid out with `breaks` set to `none`, then laid out again with the
+    /// options that were set before.
+    pub fn render_horizontal(&self) -> anyhow::Result<HorizontalScore> {
+        let options = AllOptions {
+            breaks: Some("none".into()),
+            ..Default::default()
+        };
+        let result = self.with_options(&options, |tk| {
+            tk.redo_layout("")?;
+            let svg = tk.render_to_svg(1, false)?;
+            let geometry = tk.page_geometry(1)?;
+            let timemap = tk.render_to_timemap(Some(&TimemapRenderOptions {
+                include_measures: true,
+                ..Default::default()
+            }))?;
+            Ok((svg, geometry, timemap))
+        });
+        self.redo_layout("")?;
+        let (svg, geometry, timemap) = result?;
+
+        let timemap: Vec<TimemapEntry> = serde_json::from_str(&timemap)?;
+        let mut onsets = HashMap::new();
+        for entry in timemap {
+            if let Some(measure) = entry.measure_on {
+                onsets.entry(measure).or_insert(entry.tstamp);
+            }
+        }
+
+        let view_box = geometry.view_box;
+        let to_pixels = |x: f64| (x - view_box.x) * geometry.width / view_box.width;
+        let mut measures: Vec<HorizontalMeasure> = geometry
+            .elements
+            .iter()
+            .filter(|(_, element)| element.kind == "measure")
+            .map(|(id, element)| HorizontalMeasure {
+                id: id.clone(),
+                x_start: to_pixels(element.rect.x),
+                x_end: to_pixels(element.rect.x + element.rect.width),
+                onset_ms: onsets.get(id).copied(),
+            })
+            .collect();
+        measures.sort_by(|a, b| a.x_start.total_cmp(&b.x_start));
+        Ok(HorizontalScore {
+            svg,
+            width: geometry.width,
+            height: geometry.height,
+            measures,
+        })
+    }
+}
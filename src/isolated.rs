@@ -0,0 +1,955 @@
+//! Run a [`VerovioToolkit`] in a child worker process.
+//!
+//! The parent and the worker exchange frames over the worker's stdin and stdout.
+//! A frame is a little-endian `u32` byte length followed by a JSON document.
+//! The worker side is [`run_worker`], which the `verovior-worker` binary calls.
+
+use crate::{AllOptions, ElementTime, Elements, Format, TimemapRenderOptions, VerovioToolkit};
+use anyhow::anyhow;
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, Command, ExitStatus, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+/// How to spawn and supervise the worker process.
+#[derive(Debug, Clone)]
+pub struct IsolatedConfig {
+    /// The worker executable, `verovior-worker` from `PATH` by default.
+    pub worker: PathBuf,
+    /// Resource path passed to the worker, verovio's default if `None`.
+    pub resource_path: Option<PathBuf>,
+    /// Maximum time a single call may take before the worker is killed.
+    pub timeout: Duration,
+    /// Address space limit of the worker in bytes, applied with `setrlimit`.
+    /// This is ignored on non-unix platforms.
+    pub memory_limit: Option<u64>,
+}
+
+impl Default for IsolatedConfig {
+    fn default() -> Self {
+        IsolatedConfig {
+            worker: PathBuf::from("verovior-worker"),
+            resource_path: None,
+            timeout: Duration::from_secs(30),
+            memory_limit: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+enum Request {
+    LoadData(String),
+    LoadFile(PathBuf),
+    LoadZipData(String),
+    RenderData {
+        data: String,
+        options: String,
+    },
+    SetOptions(Box<AllOptions>),
+    GetOptions,
+    ResetOptions,
+    GetAvailableOptions,
+    GetDefaultOptions,
+    GetOptionUsage,
+    SetScale(i32),
+    GetScale,
+    SetInputFrom(Format),
+    SetOutputTo(Format),
+    ResetXmlIdSeed(i32),
+    GetResourcePath,
+    RedoLayout(String),
+    RedoPagePitchPosLayout,
+    Select(String),
+    #[cfg(feature = "edit")]
+    Edit(String),
+    #[cfg(feature = "edit")]
+    EditInfo,
+    GetPageCount,
+    RenderToSvg {
+        page_no: i32,
+        xml_declaration: bool,
+    },
+    RenderToSvgFile {
+        path: PathBuf,
+        page_no: i32,
+    },
+    RenderToMidi,
+    RenderToMidiFile(String),
+    RenderToTimemap(Option<TimemapRenderOptions>),
+    RenderToTimemapFile {
+        path: PathBuf,
+        options: Option<TimemapRenderOptions>,
+    },
+    RenderToExpansionMap,
+    RenderToExpansionMapFile(String),
+    #[cfg(feature = "pae")]
+    RenderToPae,
+    #[cfg(feature = "pae")]
+    RenderToPaeFile(String),
+    #[cfg(feature = "pae")]
+    ValidatePae(String),
+    #[cfg(feature = "pae")]
+    ValidatePaeFile(PathBuf),
+    #[cfg(feature = "humdrum")]
+    GetHumdrum,
+    #[cfg(feature = "humdrum")]
+    GetHumdrumFile(String),
+    #[cfg(feature = "humdrum")]
+    ConvertHumdrumToHumdrum(String),
+    #[cfg(feature = "humdrum")]
+    ConvertHumdrumToMidi(String),
+    #[cfg(feature = "humdrum")]
+    ConvertMidiToHumdrum(String),
+    GetMei(String),
+    SaveFile {
+        path: PathBuf,
+        options: String,
+    },
+    GetElementsAtTime(i32),
+    GetPageWithElement(String),
+    GetTimeForElement(String),
+    GetTimesForElement(String),
+    GetElementAttr(String),
+    GetDescriptiveFeatures(String),
+    GetExpansionIdsForElement(String),
+    GetMidiValuesForElement(String),
+    GetNotatedIdForElement(String),
+    GetId,
+    GetVersion,
+    GetLog,
+}
+
+impl Request {
+    /// Whether the request changes toolkit state that must survive a respawn.
+    fn is_stateful(&self) -> bool {
+        #[cfg(feature = "edit")]
+        if let Request::Edit(_) = self {
+            return true;
+        }
+        self.is_load()
+            || self.is_options()
+            || matches!(
+                self,
+                Request::ResetXmlIdSeed(_)
+                    | Request::RedoLayout(_)
+                    | Request::RedoPagePitchPosLayout
+                    | Request::Select(_)
+            )
+    }
+
+    fn is_load(&self) -> bool {
+        matches!(
+            self,
+            Request::LoadData(_)
+                | Request::LoadFile(_)
+                | Request::LoadZipData(_)
+                | Request::RenderData { .. }
+        )
+    }
+
+    /// Whether the request changes the options.
+    fn is_options(&self) -> bool {
+        matches!(
+            self,
+            Request::SetOptions(_)
+                | Request::ResetOptions
+                | Request::SetScale(_)
+                | Request::SetInputFrom(_)
+                | Request::SetOutputTo(_)
+        )
+    }
+
+    /// Whether replaying `self` after `earlier`, with only option changes in between,
+    /// leaves nothing of what `earlier` did.
+    fn supersedes(&self, earlier: &Request) -> bool {
+        match self {
+            Request::SetScale(_) => matches!(earlier, Request::SetScale(_)),
+            Request::SetInputFrom(_) => matches!(earlier, Request::SetInputFrom(_)),
+            Request::SetOutputTo(_) => matches!(earlier, Request::SetOutputTo(_)),
+            Request::ResetXmlIdSeed(_) => matches!(earlier, Request::ResetXmlIdSeed(_)),
+            Request::Select(_) => matches!(earlier, Request::Select(_)),
+            Request::RedoLayout(_) => matches!(
+                earlier,
+                Request::RedoLayout(_) | Request::RedoPagePitchPosLayout
+            ),
+            _ => false,
+        }
+    }
+}
+
+/// A maximum frame size, so that a corrupt length cannot make the reader allocate
+/// gigabytes.
+const MAX_FRAME: usize = 1 << 30;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Response {
+    Ok(serde_json::Value),
+    Err(String),
+}
+
+struct Worker {
+    child: Child,
+    stdin: ChildStdin,
+    frames: Receiver<io::Result<Vec<u8>>>,
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// A [`VerovioToolkit`] living in a separate process.
+///
+/// A call that exceeds the timeout kills the worker, as does a crash of verovio.
+/// The next call spawns a new worker and replays the successful state changes
+/// (the option changes, the latest load, then the edits, layout and selection)
+/// in the order they were made before it runs. If the replay fails, the new worker is started empty instead
+/// and the call returns the error.
+pub struct IsolatedToolkit {
+    config: IsolatedConfig,
+    worker: Option<Worker>,
+    replay: Vec<Request>,
+}
+
+impl IsolatedToolkit {
+    /// Spawn a worker process with the given configuration.
+    pub fn spawn(config: IsolatedConfig) -> anyhow::Result<IsolatedToolkit> {
+        let mut toolkit = IsolatedToolkit {
+            config,
+            worker: None,
+            replay: vec![],
+        };
+        toolkit.worker = Some(toolkit.start()?);
+        Ok(toolkit)
+    }
+
+    /// Change the timeout used for subsequent calls.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.config.timeout = timeout;
+    }
+
+    pub fn load_data(&mut self, p: impl AsRef<str>) -> anyhow::Result<bool> {
+        self.call(Request::LoadData(p.as_ref().into()))
+    }
+
+    pub fn load_file(&mut self, path: impl Into<PathBuf>) -> anyhow::Result<bool> {
+        self.call(Request::LoadFile(path.into()))
+    }
+
+    pub fn load_zip_data(&mut self, p: impl AsRef<[u8]>) -> anyhow::Result<bool> {
+        self.call(Request::LoadZipData(BASE64_STANDARD.encode(p)))
+    }
+
+    pub fn render_data(
+        &mut self,
+        data: impl AsRef<str>,
+        option: impl AsRef<str>,
+    ) -> anyhow::Result<String> {
+        self.call(Request::RenderData {
+            data: data.as_ref().into(),
+            options: option.as_ref().into(),
+        })
+    }
+
+    pub fn set_options(&mut self, options: &AllOptions) -> anyhow::Result<bool> {
+        self.call(Request::SetOptions(Box::new(options.clone())))
+    }
+
+    pub fn get_options(&mut self) -> anyhow::Result<AllOptions> {
+        self.call(Request::GetOptions)
+    }
+
+    pub fn reset_options(&mut self) -> anyhow::Result<()> {
+        self.call(Request::ResetOptions)
+    }
+
+    pub fn get_available_options(&mut self) -> anyhow::Result<String> {
+        self.call(Request::GetAvailableOptions)
+    }
+
+    pub fn get_default_options(&mut self) -> anyhow::Result<String> {
+        self.call(Request::GetDefaultOptions)
+    }
+
+    pub fn get_option_usage(&mut self) -> anyhow::Result<String> {
+        self.call(Request::GetOptionUsage)
+    }
+
+    pub fn set_scale(&mut self, scale: i32) -> anyhow::Result<bool> {
+        self.call(Request::SetScale(scale))
+    }
+
+    pub fn get_scale(&mut self) -> anyhow::Result<i32> {
+        self.call(Request::GetScale)
+    }
+
+    pub fn set_input_from(&mut self, input: Format) -> anyhow::Result<bool> {
+        self.call(Request::SetInputFrom(input))
+    }
+
+    pub fn set_output_to(&mut self, format: Format) -> anyhow::Result<bool> {
+        self.call(Request::SetOutputTo(format))
+    }
+
+    pub fn reset_xml_seed(&mut self, seed: i32) -> anyhow::Result<()> {
+        self.call(Request::ResetXmlIdSeed(seed))
+    }
+
+    pub fn get_resource_path(&mut self) -> anyhow::Result<String> {
+        self.call(Request::GetResourcePath)
+    }
+
+    pub fn redo_layout(&mut self, p: impl AsRef<str>) -> anyhow::Result<()> {
+        self.call(Request::RedoLayout(p.as_ref().into()))
+    }
+
+    pub fn redo_page_pitch_pos_layout(&mut self) -> anyhow::Result<()> {
+        self.call(Request::RedoPagePitchPosLayout)
+    }
+
+    pub fn select(&mut self, select: impl AsRef<str>) -> anyhow::Result<bool> {
+        self.call(Request::Select(select.as_ref().into()))
+    }
+
+    #[cfg(feature = "edit")]
+    pub fn edit(&mut self, action: impl AsRef<str>) -> anyhow::Result<bool> {
+        self.call(Request::Edit(action.as_ref().into()))
+    }
+
+    #[cfg(feature = "edit")]
+    pub fn edit_info(&mut self) -> anyhow::Result<String> {
+        self.call(Request::EditInfo)
+    }
+
+    pub fn get_page_count(&mut self) -> anyhow::Result<i32> {
+        self.call(Request::GetPageCount)
+    }
+
+    pub fn render_to_svg(&mut self, page_no: i32, xml_declaration: bool) -> anyhow::Result<String> {
+        self.call(Request::RenderToSvg {
+            page_no,
+            xml_declaration,
+        })
+    }
+
+    pub fn render_to_svg_file(
+        &mut self,
+        path: impl Into<PathBuf>,
+        page_no: i32,
+    ) -> anyhow::Result<bool> {
+        self.call(Request::RenderToSvgFile {
+            path: path.into(),
+            page_no,
+        })
+    }
+
+    pub fn render_to_midi(&mut self) -> anyhow::Result<Vec<u8>> {
+        let s: String = self.call(Request::RenderToMidi)?;
+        Ok(BASE64_STANDARD.decode(s.as_bytes())?)
+    }
+
+    pub fn render_to_midi_file(&mut self, p: impl AsRef<str>) -> anyhow::Result<bool> {
+        self.call(Request::RenderToMidiFile(p.as_ref().into()))
+    }
+
+    pub fn render_to_timemap(
+        &mut self,
+        options: Option<&TimemapRenderOptions>,
+    ) -> anyhow::Result<String> {
+        self.call(Request::RenderToTimemap(options.cloned()))
+    }
+
+    pub fn render_to_timemap_file(
+        &mut self,
+        path: impl Into<PathBuf>,
+        options: Option<&TimemapRenderOptions>,
+    ) -> anyhow::Result<bool> {
+        self.call(Request::RenderToTimemapFile {
+            path: path.into(),
+            options: options.cloned(),
+        })
+    }
+
+    pub fn render_to_expansion_map(&mut self) -> anyhow::Result<String> {
+        self.call(Request::RenderToExpansionMap)
+    }
+
+    pub fn render_to_expansion_map_file(&mut self, p: impl AsRef<str>) -> anyhow::Result<bool> {
+        self.call(Request::RenderToExpansionMapFile(p.as_ref().into()))
+    }
+
+    #[cfg(feature = "pae")]
+    pub fn render_to_pae(&mut self) -> anyhow::Result<String> {
+        self.call(Request::RenderToPae)
+    }
+
+    #[cfg(feature = "pae")]
+    pub fn render_to_pae_file(&mut self, p: impl AsRef<str>) -> anyhow::Result<bool> {
+        self.call(Request::RenderToPaeFile(p.as_ref().into()))
+    }
+
+    #[cfg(feature = "pae")]
+    pub fn validate_pae(&mut self, pae: impl AsRef<str>) -> anyhow::Result<String> {
+        self.call(Request::ValidatePae(pae.as_ref().into()))
+    }
+
+    #[cfg(feature = "pae")]
+    pub fn validate_pae_file(&mut self, path: impl Into<PathBuf>) -> anyhow::Result<String> {
+        self.call(Request::ValidatePaeFile(path.into()))
+    }
+
+    #[cfg(feature = "humdrum")]
+    pub fn get_humdrum(&mut self) -> anyhow::Result<String> {
+        self.call(Request::GetHumdrum)
+    }
+
+    #[cfg(feature = "humdrum")]
+    pub fn get_humdrum_file(&mut self, p: impl AsRef<str>) -> anyhow::Result<bool> {
+        self.call(Request::GetHumdrumFile(p.as_ref().into()))
+    }
+
+    #[cfg(feature = "humdrum")]
+    pub fn convert_humdrum_to_humdrum(&mut self, p: impl AsRef<str>) -> anyhow::Result<String> {
+        self.call(Request::ConvertHumdrumToHumdrum(p.as_ref().into()))
+    }
+
+    #[cfg(feature = "humdrum")]
+    pub fn convert_humdrum_to_midi(&mut self, p: impl AsRef<str>) -> anyhow::Result<Vec<u8>> {
+        let s: String = self.call(Request::ConvertHumdrumToMidi(p.as_ref().into()))?;
+        Ok(BASE64_STANDARD.decode(s.as_bytes())?)
+    }
+
+    #[cfg(feature = "humdrum")]
+    pub fn convert_midi_to_humdrum(&mut self, p: impl AsRef<str>) -> anyhow::Result<String> {
+        self.call(Request::ConvertMidiToHumdrum(p.as_ref().into()))
+    }
+
+    pub fn get_mei(&mut self, options: impl AsRef<str>) -> anyhow::Result<String> {
+        self.call(Request::GetMei(options.as_ref().into()))
+    }
+
+    pub fn save_file(
+        &mut self,
+        path: impl Into<PathBuf>,
+        options: impl AsRef<str>,
+    ) -> anyhow::Result<bool> {
+        self.call(Request::SaveFile {
+            path: path.into(),
+            options: options.as_ref().into(),
+        })
+    }
+
+    pub fn get_elements_at_time(&mut self, p: i32) -> anyhow::Result<Elements> {
+        self.call(Request::GetElementsAtTime(p))
+    }
+
+    pub fn get_page_with_element(&mut self, p: impl AsRef<str>) -> anyhow::Result<i32> {
+        self.call(Request::GetPageWithElement(p.as_ref().into()))
+    }
+
+    pub fn get_time_for_element(&mut self, p: impl AsRef<str>) -> anyhow::Result<f64> {
+        self.call(Request::GetTimeForElement(p.as_ref().into()))
+    }
+
+    pub fn get_times_for_element(&mut self, p: impl AsRef<str>) -> anyhow::Result<ElementTime> {
+        self.call(Request::GetTimesForElement(p.as_ref().into()))
+    }
+
+    pub fn get_element_attr(&mut self, p: impl AsRef<str>) -> anyhow::Result<String> {
+        self.call(Request::GetElementAttr(p.as_ref().into()))
+    }
+
+    pub fn get_descriptive_features(&mut self, p: impl AsRef<str>) -> anyhow::Result<String> {
+        self.call(Request::GetDescriptiveFeatures(p.as_ref().into()))
+    }
+
+    pub fn get_expansion_ids_for_element(&mut self, p: impl AsRef<str>) -> anyhow::Result<String> {
+        self.call(Request::GetExpansionIdsForElement(p.as_ref().into()))
+    }
+
+    pub fn get_midi_values_for_element(&mut self, p: impl AsRef<str>) -> anyhow::Result<String> {
+        self.call(Request::GetMidiValuesForElement(p.as_ref().into()))
+    }
+
+    pub fn get_notated_id_for_element(&mut self, p: impl AsRef<str>) -> anyhow::Result<String> {
+        self.call(Request::GetNotatedIdForElement(p.as_ref().into()))
+    }
+
+    pub fn get_id(&mut self) -> anyhow::Result<String> {
+        self.call(Request::GetId)
+    }
+
+    pub fn get_version(&mut self) -> anyhow::Result<String> {
+        self.call(Request::GetVersion)
+    }
+
+    pub fn get_log(&mut self) -> anyhow::Result<String> {
+        self.call(Request::GetLog)
+    }
+
+    fn call<T: DeserializeOwned>(&mut self, request: Request) -> anyhow::Result<T> {
+        let mut worker = match self.worker.take() {
+            Some(worker) => worker,
+            None => self.restart()?,
+        };
+        let ret = transact(&mut worker, &request, self.config.timeout);
+        match ret {
+            Ok(Ok(value)) => {
+                if request.is_stateful() {
+                    self.record(request);
+                }
+                self.worker = Some(worker);
+                Ok(serde_json::from_value(value)?)
+            }
+            Ok(Err(message)) => {
+                self.worker = Some(worker);
+                Err(anyhow!(message))
+            }
+            // The worker is dropped, and thus killed, on transport errors.
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Add a successful state change to the replay.
+    ///
+    /// A load replaces the document, so the edits, layout and selection made before it
+    /// are dropped, keeping the option changes it was made with. Requests superseded by
+    /// `request` are dropped too, and consecutive `SetOptions` merged, so that the replay
+    /// of a viewer resizing in a loop stays short.
+    fn record(&mut self, request: Request) {
+        if request.is_load() {
+            self.replay
+                .retain(|r| r.is_options() || matches!(r, Request::ResetXmlIdSeed(_)));
+        }
+        let mut i = self.replay.len();
+        while i > 0 {
+            i -= 1;
+            if request.supersedes(&self.replay[i]) {
+                self.replay.remove(i);
+            } else if !self.replay[i].is_options() {
+                break;
+            }
+        }
+        self.replay.push(request);
+        self.replay
+            .dedup_by(|later, earlier| match (earlier, later) {
+                (Request::SetOptions(earlier), Request::SetOptions(later)) => {
+                    merge_options(earlier, later)
+                }
+                _ => false,
+            });
+    }
+
+    /// Start a worker and replay the state into it. If that fails, the state is
+    /// dropped and an empty worker is kept for the next call.
+    fn restart(&mut self) -> anyhow::Result<Worker> {
+        let mut worker = self.start()?;
+        let replayed = self.replay.iter().try_for_each(|request| {
+            match transact(&mut worker, request, self.config.timeout)? {
+                Ok(_) => Ok(()),
+                Err(message) => Err(anyhow!(message)),
+            }
+        });
+        match replayed {
+            Ok(()) => Ok(worker),
+            Err(err) => {
+                drop(worker);
+                self.replay.clear();
+                self.worker = Some(self.start()?);
+                Err(err.context("replaying state in a new worker, which was started empty"))
+            }
+        }
+    }
+
+    fn start(&self) -> anyhow::Result<Worker> {
+        let mut command = Command::new(&self.config.worker);
+        if let Some(path) = &self.config.resource_path {
+            command.arg(path);
+        }
+        command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit());
+        #[cfg(unix)]
+        if let Some(limit) = self.config.memory_limit {
+            use std::os::unix::process::CommandExt;
+            unsafe {
+                command.pre_exec(move || {
+                    let rlimit = libc::rlimit {
+                        rlim_cur: limit as libc::rlim_t,
+                        rlim_max: limit as libc::rlim_t,
+                    };
+                    if libc::setrlimit(libc::RLIMIT_AS, &rlimit) != 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                    Ok(())
+                });
+            }
+        }
+        let mut child = command.spawn()?;
+        let stdin = child.stdin.take().expect("piped stdin");
+        let mut stdout = child.stdout.take().expect("piped stdout");
+        let (sender, frames) = mpsc::channel();
+        thread::spawn(move || {
+            while let Some(frame) = read_frame(&mut stdout).transpose() {
+                let failed = frame.is_err();
+                if sender.send(frame).is_err() || failed {
+                    break;
+                }
+            }
+        });
+        Ok(Worker {
+            child,
+            stdin,
+            frames,
+        })
+    }
+}
+
+/// Set the options of `later` in `earlier`, returning whether that worked.
+fn merge_options(earlier: &mut AllOptions, later: &AllOptions) -> bool {
+    let (Ok(serde_json::Value::Object(mut merged)), Ok(serde_json::Value::Object(later))) =
+        (serde_json::to_value(&*earlier), serde_json::to_value(later))
+    else {
+        return false;
+    };
+    merged.extend(later.into_iter().filter(|(_, value)| !value.is_null()));
+    match serde_json::from_value(serde_json::Value::Object(merged)) {
+        Ok(options) => {
+            *earlier = options;
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// Send one request and wait for its response.
+/// The outer error means the worker is unusable and has to be replaced.
+fn transact(
+    worker: &mut Worker,
+    request: &Request,
+    timeout: Duration,
+) -> anyhow::Result<Result<serde_json::Value, String>> {
+    let frame = serde_json::to_vec(request)?;
+    if let Err(err) = write_frame(&mut worker.stdin, &frame) {
+        return Err(worker_gone(worker, err.into()));
+    }
+    match worker.frames.recv_timeout(timeout) {
+        Ok(Ok(frame)) => match serde_json::from_slice(&frame)? {
+            Response::Ok(value) => Ok(Ok(value)),
+            Response::Err(message) => Ok(Err(message)),
+        },
+        Ok(Err(err)) => Err(worker_gone(worker, err.into())),
+        Err(RecvTimeoutError::Timeout) => {
+            let _ = worker.child.kill();
+            Err(anyhow!("verovio worker timed out after {:?}", timeout))
+        }
+        Err(RecvTimeoutError::Disconnected) => {
+            Err(worker_gone(worker, anyhow!("unexpected end of output")))
+        }
+    }
+}
+
+fn worker_gone(worker: &mut Worker, err: anyhow::Error) -> anyhow::Error {
+    match worker.child.wait() {
+        Ok(status) => anyhow!("verovio worker exited with {}: {}", describe(status), err),
+        Err(_) => err.context("verovio worker failed"),
+    }
+}
+
+fn describe(status: ExitStatus) -> String {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return format!("signal {}", signal);
+        }
+    }
+    status.to_string()
+}
+
+fn read_frame(r: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0u8; 4];
+    // The end of the stream is only clean between frames.
+    match r.read(&mut len[..1]) {
+        Ok(0) => return Ok(None),
+        Ok(_) => r.read_exact(&mut len[1..])?,
+        Err(err) => return Err(err),
+    }
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_FRAME {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("frame of {} bytes is too large", len),
+        ));
+    }
+    let mut frame = vec![0; len];
+    r.read_exact(&mut frame)?;
+    Ok(Some(frame))
+}
+
+fn write_frame(w: &mut impl Write, frame: &[u8]) -> io::Result<()> {
+    if frame.len() > MAX_FRAME {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("frame of {} bytes is too large", frame.len()),
+        ));
+    }
+    w.write_all(&(frame.len() as u32).to_le_bytes())?;
+    w.write_all(frame)?;
+    w.flush()
+}
+
+/// Serve requests from stdin until it is closed.
+///
+/// The first command line argument, if any, is used as the resource path.
+/// Verovio logs to a buffer so that stdout only carries frames.
+pub fn run_worker() -> anyhow::Result<()> {
+    VerovioToolkit::enable_log_to_buffer(true);
+    let toolkit = match std::env::args_os().nth(1) {
+        Some(path) => VerovioToolkit::with_resource(path)?,
+        None => VerovioToolkit::new(),
+    };
+    let mut stdin = io::stdin().lock();
+    let mut stdout = io::stdout().lock();
+    while let Some(frame) = read_frame(&mut stdin)? {
+        let response = match serde_json::from_slice(&frame) {
+            Ok(request) => match handle(&toolkit, request) {
+                Ok(value) => Response::Ok(value),
+                Err(err) => Response::Err(format!("{:#}", err)),
+            },
+            Err(err) => Response::Err(format!("malformed request: {}", err)),
+        };
+        write_frame(&mut stdout, &serde_json::to_vec(&response)?)?;
+    }
+    Ok(())
+}
+
+fn handle(toolkit: &VerovioToolkit, request: Request) -> anyhow::Result<serde_json::Value> {
+    let value = match request {
+        Request::LoadData(data) => serde_json::to_value(toolkit.load_data(data)?)?,
+        Request::LoadFile(path) => serde_json::to_value(toolkit.load_file(path)?)?,
+        Request::LoadZipData(data) => {
+            let data = BASE64_STANDARD.decode(data.as_bytes())?;
+            serde_json::to_value(toolkit.load_zip_data(data)?)?
+        }
+        Request::RenderData { data, options } => {
+            serde_json::to_value(toolkit.render_data(data, options)?)?
+        }
+        Request::SetOptions(options) => serde_json::to_value(toolkit.set_options(&options)?)?,
+        Request::GetOptions => serde_json::to_value(toolkit.get_options()?)?,
+        Request::ResetOptions => serde_json::to_value(toolkit.reset_options()?)?,
+        Request::GetAvailableOptions => serde_json::to_value(toolkit.get_available_options()?)?,
+        Request::GetDefaultOptions => serde_json::to_value(toolkit.get_default_options()?)?,
+        Request::GetOptionUsage => serde_json::to_value(toolkit.get_option_usage()?)?,
+        Request::SetScale(scale) => serde_json::to_value(toolkit.set_scale(scale)?)?,
+        Request::GetScale => serde_json::to_value(toolkit.get_scale()?)?,
+        Request::SetInputFrom(input) => serde_json::to_value(toolkit.set_input_from(input)?)?,
+        Request::SetOutputTo(format) => serde_json::to_value(toolkit.set_output_to(format)?)?,
+        Request::ResetXmlIdSeed(seed) => serde_json::to_value(toolkit.reset_xml_seed(seed)?)?,
+        Request::GetResourcePath => serde_json::to_value(toolkit.get_resource_path()?)?,
+        Request::RedoLayout(options) => serde_json::to_value(toolkit.redo_layout(options)?)?,
+        Request::RedoPagePitchPosLayout => {
+            serde_json::to_value(toolkit.redo_page_pitch_pos_layout()?)?
+        }
+        Request::Select(select) => serde_json::to_value(toolkit.select(select)?)?,
+        #[cfg(feature = "edit")]
+        Request::Edit(action) => serde_json::to_value(toolkit.edit(action)?)?,
+        #[cfg(feature = "edit")]
+        Request::EditInfo => serde_json::to_value(toolkit.edit_info()?)?,
+        Request::GetPageCount => serde_json::to_value(toolkit.get_page_count()?)?,
+        Request::RenderToSvg {
+            page_no,
+            xml_declaration,
+        } => serde_json::to_value(toolkit.render_to_svg(page_no, xml_declaration)?)?,
+        Request::RenderToSvgFile { path, page_no } => {
+            serde_json::to_value(toolkit.render_to_svg_file(path, page_no)?)?
+        }
+        Request::RenderToMidi => {
+            serde_json::to_value(BASE64_STANDARD.encode(toolkit.render_to_midi()?))?
+        }
+        Request::RenderToMidiFile(path) => {
+            serde_json::to_value(toolkit.render_to_midi_file(path)?)?
+        }
+        Request::RenderToTimemap(options) => {
+            serde_json::to_value(toolkit.render_to_timemap(options.as_ref())?)?
+        }
+        Request::RenderToTimemapFile { path, options } => {
+            serde_json::to_value(toolkit.render_to_timemap_file(path, options.as_ref())?)?
+        }
+        Request::RenderToExpansionMap => serde_json::to_value(toolkit.render_to_expansion_map()?)?,
+        Request::RenderToExpansionMapFile(path) => {
+            serde_json::to_value(toolkit.render_to_expansion_map_file(path)?)?
+        }
+        #[cfg(feature = "pae")]
+        Request::RenderToPae => serde_json::to_value(toolkit.render_to_pae()?)?,
+        #[cfg(feature = "pae")]
+        Request::RenderToPaeFile(path) => serde_json::to_value(toolkit.render_to_pae_file(path)?)?,
+        #[cfg(feature = "pae")]
+        Request::ValidatePae(pae) => serde_json::to_value(toolkit.validate_pae(pae)?)?,
+        #[cfg(feature = "pae")]
+        Request::ValidatePaeFile(path) => serde_json::to_value(toolkit.validate_pae_file(path)?)?,
+        #[cfg(feature = "humdrum")]
+        Request::GetHumdrum => serde_json::to_value(toolkit.get_humdrum()?)?,
+        #[cfg(feature = "humdrum")]
+        Request::GetHumdrumFile(path) => serde_json::to_value(toolkit.get_humdrum_file(path)?)?,
+        #[cfg(feature = "humdrum")]
+        Request::ConvertHumdrumToHumdrum(data) => {
+            serde_json::to_value(toolkit.convert_humdrum_to_humdrum(data)?)?
+        }
+        #[cfg(feature = "humdrum")]
+        Request::ConvertHumdrumToMidi(data) => {
+            serde_json::to_value(BASE64_STANDARD.encode(toolkit.convert_humdrum_to_midi(data)?))?
+        }
+        #[cfg(feature = "humdrum")]
+        Request::ConvertMidiToHumdrum(data) => {
+            serde_json::to_value(toolkit.convert_midi_to_humdrum(data)?)?
+        }
+        Request::GetMei(options) => serde_json::to_value(toolkit.get_mei(options)?)?,
+        Request::SaveFile { path, options } => {
+            serde_json::to_value(toolkit.save_file(path, options)?)?
+        }
+        Request::GetElementsAtTime(time) => {
+            serde_json::to_value(toolkit.get_elements_at_time(time)?)?
+        }
+        Request::GetPageWithElement(id) => {
+            serde_json::to_value(toolkit.get_page_with_element(id)?)?
+        }
+        Request::GetTimeForElement(id) => serde_json::to_value(toolkit.get_time_for_element(id)?)?,
+        Request::GetTimesForElement(id) => {
+            serde_json::to_value(toolkit.get_times_for_element(id)?)?
+        }
+        Request::GetElementAttr(id) => serde_json::to_value(toolkit.get_element_attr(id)?)?,
+        Request::GetDescriptiveFeatures(options) => {
+            serde_json::to_value(toolkit.get_descriptive_features(options)?)?
+        }
+        Request::GetExpansionIdsForElement(id) => {
+            serde_json::to_value(toolkit.get_expansion_ids_for_element(id)?)?
+        }
+        Request::GetMidiValuesForElement(id) => {
+            serde_json::to_value(toolkit.get_midi_values_for_element(id)?)?
+        }
+        Request::GetNotatedIdForElement(id) => {
+            serde_json::to_value(toolkit.get_notated_id_for_element(id)?)?
+        }
+        Request::GetId => serde_json::to_value(toolkit.get_id()?)?,
+        Request::GetVersion => serde_json::to_value(toolkit.get_version()?)?,
+        Request::GetLog => serde_json::to_value(toolkit.get_log()?)?,
+    };
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::io::Cursor;
+
+    #[test]
+    fn frames() {
+        let mut buffer = vec![];
+        write_frame(&mut buffer, b"{}").unwrap();
+        write_frame(&mut buffer, b"").unwrap();
+        assert_eq!(&buffer[..6], b"\x02\0\0\0{}");
+        let mut r = Cursor::new(buffer);
+        assert_eq!(read_frame(&mut r).unwrap(), Some(b"{}".to_vec()));
+        assert_eq!(read_frame(&mut r).unwrap(), Some(vec![]));
+        assert_eq!(read_frame(&mut r).unwrap(), None);
+    }
+
+    #[test]
+    fn truncated_and_oversized_frames() {
+        let error = |bytes: &[u8]| read_frame(&mut Cursor::new(bytes)).unwrap_err().kind();
+        assert_eq!(error(b"\x05\0"), io::ErrorKind::UnexpectedEof);
+        assert_eq!(error(b"\x05\0\0\0abc"), io::ErrorKind::UnexpectedEof);
+        assert_eq!(error(&u32::MAX.to_le_bytes()), io::ErrorKind::InvalidData);
+    }
+
+    fn toolkit() -> IsolatedToolkit {
+        IsolatedToolkit {
+            config: IsolatedConfig::default(),
+            worker: None,
+            replay: vec![],
+        }
+    }
+
+    fn options(scale: Option<i32>, input_from: Option<&str>) -> Request {
+        Request::SetOptions(Box::new(AllOptions {
+            scale,
+            input_from: input_from.map(String::from),
+            ..Default::default()
+        }))
+    }
+
+    /// The replay as method names, with the scale and input format of `SetOptions`.
+    fn replay(toolkit: &IsolatedToolkit) -> Vec<serde_json::Value> {
+        toolkit
+            .replay
+            .iter()
+            .map(|request| {
+                let value = serde_json::to_value(request).unwrap();
+                match request {
+                    Request::SetOptions(options) => {
+                        json!(["set_options", options.scale, options.input_from])
+                    }
+                    _ => json!([value["method"], value["params"]]),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn replays_options_in_order_with_the_latest_load() {
+        let mut toolkit = toolkit();
+        toolkit.record(options(None, Some("abc")));
+        toolkit.record(Request::LoadData("X:1".into()));
+        toolkit.record(Request::Select("{}".into()));
+        toolkit.record(options(None, Some("mei")));
+        toolkit.record(Request::LoadData("<mei/>".into()));
+        toolkit.record(options(Some(50), None));
+        toolkit.record(Request::RedoLayout("".into()));
+        assert_eq!(
+            replay(&toolkit),
+            [
+                // The options of both loads, merged.
+                json!(["set_options", null, "mei"]),
+                json!(["load_data", "<mei/>"]),
+                json!(["set_options", 50, null]),
+                json!(["redo_layout", ""]),
+            ]
+        );
+    }
+
+    #[test]
+    fn compacts_the_replay() {
+        let mut toolkit = toolkit();
+        toolkit.record(Request::SetInputFrom(Format::Mei));
+        toolkit.record(Request::LoadData("<mei/>".into()));
+        for scale in [40, 50, 60] {
+            toolkit.record(options(Some(scale), None));
+            toolkit.record(Request::RedoLayout("".into()));
+        }
+        toolkit.record(Request::SetScale(30));
+        toolkit.record(Request::SetScale(35));
+        assert_eq!(
+            replay(&toolkit),
+            [
+                json!(["set_input_from", "Mei"]),
+                json!(["load_data", "<mei/>"]),
+                json!(["set_options", 60, null]),
+                json!(["redo_layout", ""]),
+                json!(["set_scale", 35]),
+            ]
+        );
+    }
+}
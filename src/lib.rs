@@ -1,43 +1,197 @@
-use anyhow;
 use base64::prelude::BASE64_STANDARD;
 use base64::Engine;
 use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
 use std::ffi::{CStr, CString};
-use std::path::Path;
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+mod capabilities;
+mod coloring;
+mod excerpt;
+mod fit;
+#[cfg(feature = "pdf")]
+mod font_subset;
+mod fonts;
+mod geometry;
+mod hit;
+mod horizontal;
+pub mod isolated;
+mod page_index;
+mod pages;
+#[cfg(feature = "pdf")]
+mod pdf;
+#[cfg(feature = "pdf")]
+mod pdf_text;
+mod pitch;
+#[cfg(feature = "raster")]
+mod raster;
+#[cfg(feature = "embedded-resources")]
+mod resources;
+mod styling;
+mod svg;
+#[cfg(any(feature = "raster", feature = "pdf"))]
+mod svg_tree;
+mod text_fonts;
+mod theme;
+mod thumbnail;
+#[cfg(any(feature = "raster", feature = "pdf"))]
+mod woff2;
+
+pub use capabilities::Capabilities;
+pub use coloring::{ColorBy, Coloring};
+pub use fit::Fit;
+pub use fonts::MissingGlyphs;
+pub use geometry::{ElementGeometry, PageGeometry, Rect};
+pub use hit::{ElementHit, ElementIndex};
+pub use horizontal::{HorizontalMeasure, HorizontalScore};
+pub use isolated::{IsolatedConfig, IsolatedToolkit};
+pub use page_index::{PageIndex, PageLocation};
+pub use pages::{Page, Pages};
+#[cfg(feature = "pdf")]
+pub use pdf::PdfOptions;
+#[cfg(feature = "raster")]
+pub use raster::RasterOptions;
+#[cfg(feature = "embedded-resources")]
+pub use resources::embedded_resource_path;
+#[cfg(feature = "raster")]
+pub use resvg::tiny_skia::Pixmap;
+pub use styling::{Style, Styling};
+pub use svg::InlineOptions;
+pub use theme::Theme;
 
 mod bindings {
     #![allow(non_snake_case, non_camel_case_types, non_upper_case_globals, unused)]
+    #[cfg(feature = "bindgen")]
     include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+    #[cfg(not(feature = "bindgen"))]
+    include!("bindings.rs");
 }
 
 pub struct VerovioToolkit {
     tk_ptr: *mut ::std::os::raw::c_void,
+    /// Font zips written by [`VerovioToolkit::add_font`], removed on drop.
+    custom_fonts: RefCell<Vec<PathBuf>>,
+    /// Built by [`VerovioToolkit::build_page_index`], cleared when the layout may change.
+    page_index: RefCell<Option<Rc<PageIndex>>>,
+    /// The last selection made with [`VerovioToolkit::select`], cleared by loading a document.
+    selection: RefCell<Option<String>>,
+}
+
+impl Default for VerovioToolkit {
+    fn default() -> Self {
+        VerovioToolkit::new()
+    }
 }
 
 impl Drop for VerovioToolkit {
     fn drop(&mut self) {
+        // There is no one left to report a failure to.
         unsafe {
-            bindings::vrvToolkit_destructor(self.tk_ptr);
+            bindings::vrvShim_destructor(self.tk_ptr);
         }
+        fonts::remove_custom_fonts(&self.custom_fonts.borrow());
     }
 }
 
 impl VerovioToolkit {
     /// Create a new VerovioToolkit instance.
     /// This function will log some errors, if the default resource path is not found.
+    ///
+    /// The resource path is chosen as in [`VerovioToolkit::try_new`], falling back to
+    /// the next one when the resources of `VEROVIO_RESOURCE_PATH` or the embedded
+    /// resources cannot be loaded.
+    ///
+    /// # Panics
+    /// Panics if verovio throws an exception while constructing the toolkit.
     pub fn new() -> VerovioToolkit {
-        VerovioToolkit {
-            tk_ptr: unsafe { bindings::vrvToolkit_constructor() },
+        if let Some(path) = std::env::var_os("VEROVIO_RESOURCE_PATH")
+            && let Ok(toolkit) = VerovioToolkit::with_resource(path)
+        {
+            return toolkit;
         }
+        #[cfg(feature = "embedded-resources")]
+        if let Ok(path) = embedded_resource_path()
+            && let Ok(toolkit) = VerovioToolkit::with_resource(path)
+        {
+            return toolkit;
+        }
+        let mut tk_ptr = std::ptr::null_mut();
+        check(unsafe { bindings::vrvShim_constructor(&mut tk_ptr) })
+            .expect("failed to construct verovio toolkit");
+        VerovioToolkit::from_ptr(tk_ptr)
+    }
+
+    /// Create a new VerovioToolkit instance, checking that its resources are usable.
+    ///
+    /// The resource path is taken from the `VEROVIO_RESOURCE_PATH` environment variable,
+    /// then from the embedded resources with the `embedded-resources` feature,
+    /// and is verovio's default resource path otherwise.
+    pub fn try_new() -> Result<VerovioToolkit, ResourceError> {
+        let toolkit = VerovioToolkit::without_resource().map_err(ResourceError::Toolkit)?;
+        let path = match std::env::var_os("VEROVIO_RESOURCE_PATH") {
+            Some(path) => PathBuf::from(path),
+            #[cfg(feature = "embedded-resources")]
+            None => embedded_resource_path()
+                .map_err(ResourceError::Toolkit)?
+                .to_path_buf(),
+            #[cfg(not(feature = "embedded-resources"))]
+            None => PathBuf::from(
+                toolkit
+                    .get_resource_path()
+                    .map_err(ResourceError::Toolkit)?,
+            ),
+        };
+        toolkit.load_resources(&path)?;
+        Ok(toolkit)
     }
 
     /// Create a new VerovioToolkit instance with a custom resource path.
-    pub fn with_resource(path: impl AsRef<Path>) -> anyhow::Result<VerovioToolkit> {
-        let path = path.as_ref().as_os_str().as_encoded_bytes();
-        let path = CString::new(path)?;
-        Ok(VerovioToolkit {
-            tk_ptr: unsafe { bindings::vrvToolkit_constructorResourcePath(path.as_ptr()) },
-        })
+    /// The path must contain the default fonts and the text font, and the default font must load.
+    pub fn with_resource(path: impl AsRef<Path>) -> Result<VerovioToolkit, ResourceError> {
+        let toolkit = VerovioToolkit::without_resource().map_err(ResourceError::Toolkit)?;
+        toolkit.load_resources(path.as_ref())?;
+        Ok(toolkit)
+    }
+
+    fn without_resource() -> anyhow::Result<VerovioToolkit> {
+        let mut tk_ptr = std::ptr::null_mut();
+        check(unsafe { bindings::vrvShim_constructorNoResource(&mut tk_ptr) })?;
+        Ok(VerovioToolkit::from_ptr(tk_ptr))
+    }
+
+    fn from_ptr(tk_ptr: *mut ::std::os::raw::c_void) -> VerovioToolkit {
+        VerovioToolkit {
+            tk_ptr,
+            custom_fonts: RefCell::new(vec![]),
+            page_index: RefCell::new(None),
+            selection: RefCell::new(None),
+        }
+    }
+
+    fn load_resources(&self, path: &Path) -> Result<(), ResourceError> {
+        let missing: Vec<PathBuf> = REQUIRED_RESOURCES
+            .iter()
+            .map(|file| path.join(file))
+            .filter(|file| !file.exists())
+            .collect();
+        if !missing.is_empty() {
+            return Err(ResourceError::Missing {
+                path: path.to_path_buf(),
+                files: missing,
+            });
+        }
+        if !self
+            .set_resource_path(path)
+            .map_err(ResourceError::Toolkit)?
+        {
+            return Err(ResourceError::FontLoad {
+                path: path.to_path_buf(),
+            });
+        }
+        Ok(())
     }
 
     /// Enable or disable logging.
@@ -55,157 +209,191 @@ impl VerovioToolkit {
         }
     }
 
+    #[cfg(feature = "edit")]
     pub fn edit(&self, action: impl AsRef<str>) -> anyhow::Result<bool> {
-        get_primitive2(self.tk_ptr, action, bindings::vrvToolkit_edit)
+        self.invalidate_layout();
+        get_primitive2(self.tk_ptr, action, bindings::vrvShim_edit)
     }
 
     /// Get the edit info.
-    pub fn edit_info(&self) -> String {
-        get_string(self.tk_ptr, bindings::vrvToolkit_editInfo)
+    #[cfg(feature = "edit")]
+    pub fn edit_info(&self) -> anyhow::Result<String> {
+        get_string(self.tk_ptr, bindings::vrvShim_editInfo)
     }
 
-    pub fn get_available_options(&self) -> String {
-        get_string(self.tk_ptr, bindings::vrvToolkit_getAvailableOptions)
+    pub fn get_available_options(&self) -> anyhow::Result<String> {
+        get_string(self.tk_ptr, bindings::vrvShim_getAvailableOptions)
     }
 
-    pub fn get_default_options(&self) -> String {
-        get_string(self.tk_ptr, bindings::vrvToolkit_getDefaultOptions)
+    pub fn get_default_options(&self) -> anyhow::Result<String> {
+        get_string(self.tk_ptr, bindings::vrvShim_getDefaultOptions)
     }
 
     pub fn get_descriptive_features(&self, p: impl AsRef<str>) -> anyhow::Result<String> {
-        get_string2(self.tk_ptr, p, bindings::vrvToolkit_getDescriptiveFeatures)
+        get_string2(self.tk_ptr, p, bindings::vrvShim_getDescriptiveFeatures)
     }
 
     pub fn get_element_attr(&self, p: impl AsRef<str>) -> anyhow::Result<String> {
-        get_string2(self.tk_ptr, p, bindings::vrvToolkit_getElementAttr)
+        get_string2(self.tk_ptr, p, bindings::vrvShim_getElementAttr)
     }
 
     pub fn get_expansion_ids_for_element(&self, p: impl AsRef<str>) -> anyhow::Result<String> {
-        get_string2(
-            self.tk_ptr,
-            p,
-            bindings::vrvToolkit_getExpansionIdsForElement,
-        )
+        get_string2(self.tk_ptr, p, bindings::vrvShim_getExpansionIdsForElement)
     }
 
-    pub fn get_humdrum(&self) -> String {
-        get_string(self.tk_ptr, bindings::vrvToolkit_getHumdrum)
+    #[cfg(feature = "humdrum")]
+    pub fn get_humdrum(&self) -> anyhow::Result<String> {
+        get_string(self.tk_ptr, bindings::vrvShim_getHumdrum)
     }
 
+    #[cfg(feature = "humdrum")]
     pub fn get_humdrum_file(&self, p: impl AsRef<str>) -> anyhow::Result<bool> {
-        get_primitive2(self.tk_ptr, p, bindings::vrvToolkit_getHumdrumFile)
+        get_primitive2(self.tk_ptr, p, bindings::vrvShim_getHumdrumFile)
     }
 
     pub fn get_elements_at_time(&self, p: i32) -> anyhow::Result<Elements> {
         unsafe {
-            let ret = bindings::vrvToolkit_getElementsAtTime(self.tk_ptr, p);
+            let mut ret = std::ptr::null();
+            check(bindings::vrvShim_getElementsAtTime(
+                self.tk_ptr,
+                p,
+                &mut ret,
+            ))?;
             let s = CStr::from_ptr(ret).to_string_lossy();
             let ret = serde_json::from_str(&s)?;
             Ok(ret)
         }
     }
 
-    pub fn get_id(&self) -> String {
-        get_string(self.tk_ptr, bindings::vrvToolkit_getID)
+    pub fn get_id(&self) -> anyhow::Result<String> {
+        get_string(self.tk_ptr, bindings::vrvShim_getID)
     }
 
+    #[cfg(feature = "humdrum")]
     pub fn convert_humdrum_to_humdrum(&self, p: impl AsRef<str>) -> anyhow::Result<String> {
-        get_string2(self.tk_ptr, p, bindings::vrvToolkit_convertHumdrumToHumdrum)
+        get_string2(self.tk_ptr, p, bindings::vrvShim_convertHumdrumToHumdrum)
     }
 
+    #[cfg(feature = "humdrum")]
     pub fn convert_humdrum_to_midi(&self, p: impl AsRef<str>) -> anyhow::Result<Vec<u8>> {
-        let ret = get_string2(self.tk_ptr, p, bindings::vrvToolkit_convertHumdrumToMIDI)
+        let ret = get_string2(self.tk_ptr, p, bindings::vrvShim_convertHumdrumToMIDI)
             .map(|s| BASE64_STANDARD.decode(s.as_bytes()))??;
         Ok(ret)
     }
+    #[cfg(feature = "humdrum")]
     pub fn convert_midi_to_humdrum(&self, p: impl AsRef<str>) -> anyhow::Result<String> {
-        get_string2(self.tk_ptr, p, bindings::vrvToolkit_convertMEIToHumdrum)
+        get_string2(self.tk_ptr, p, bindings::vrvShim_convertMEIToHumdrum)
     }
 
-    pub fn get_log(&self) -> String {
-        get_string(self.tk_ptr, bindings::vrvToolkit_getLog)
+    pub fn get_log(&self) -> anyhow::Result<String> {
+        get_string(self.tk_ptr, bindings::vrvShim_getLog)
     }
 
     pub fn get_mei(&self, options: impl AsRef<str>) -> anyhow::Result<String> {
-        get_string2(self.tk_ptr, options, bindings::vrvToolkit_getMEI)
+        get_string2(self.tk_ptr, options, bindings::vrvShim_getMEI)
     }
 
     pub fn get_midi_values_for_element(&self, p: impl AsRef<str>) -> anyhow::Result<String> {
-        get_string2(self.tk_ptr, p, bindings::vrvToolkit_getMIDIValuesForElement)
+        get_string2(self.tk_ptr, p, bindings::vrvShim_getMIDIValuesForElement)
     }
 
     pub fn get_notated_id_for_element(&self, p: impl AsRef<str>) -> anyhow::Result<String> {
-        get_string2(self.tk_ptr, p, bindings::vrvToolkit_getNotatedIdForElement)
+        get_string2(self.tk_ptr, p, bindings::vrvShim_getNotatedIdForElement)
     }
 
     pub fn get_options(&self) -> anyhow::Result<AllOptions> {
-        let s = get_string(self.tk_ptr, bindings::vrvToolkit_getOptions);
+        let s = get_string(self.tk_ptr, bindings::vrvShim_getOptions)?;
         let options = serde_json::from_str(&s)?;
         Ok(options)
     }
 
-    pub fn get_option_usage(&self) -> String {
-        get_string(self.tk_ptr, bindings::vrvToolkit_getOptionUsageString)
+    pub fn get_option_usage(&self) -> anyhow::Result<String> {
+        get_string(self.tk_ptr, bindings::vrvShim_getOptionUsageString)
     }
 
-    pub fn get_page_count(&self) -> i32 {
-        unsafe { bindings::vrvToolkit_getPageCount(self.tk_ptr) }
+    pub fn get_page_count(&self) -> anyhow::Result<i32> {
+        get_primitive(self.tk_ptr, bindings::vrvShim_getPageCount)
+    }
+
+    /// Fail unless `page_no` is a page of the loaded document.
+    fn check_page(&self, page_no: i32) -> anyhow::Result<()> {
+        let count = self.get_page_count()?;
+        if page_no < 1 || page_no > count {
+            return Err(anyhow::anyhow!(
+                "page {} is out of range, the document has {} pages",
+                page_no,
+                count
+            ));
+        }
+        Ok(())
     }
 
     pub fn get_page_with_element(&self, p: impl AsRef<str>) -> anyhow::Result<i32> {
-        get_primitive2(self.tk_ptr, p, bindings::vrvToolkit_getPageWithElement)
+        get_primitive2(self.tk_ptr, p, bindings::vrvShim_getPageWithElement)
     }
 
-    pub fn get_resource_path(&self) -> String {
-        get_string(self.tk_ptr, bindings::vrvToolkit_getResourcePath)
+    pub fn get_resource_path(&self) -> anyhow::Result<String> {
+        get_string(self.tk_ptr, bindings::vrvShim_getResourcePath)
     }
 
-    pub fn get_scale(&self) -> i32 {
-        unsafe { bindings::vrvToolkit_getScale(self.tk_ptr) }
+    pub fn get_scale(&self) -> anyhow::Result<i32> {
+        get_primitive(self.tk_ptr, bindings::vrvShim_getScale)
     }
 
     pub fn get_time_for_element(&self, p: impl AsRef<str>) -> anyhow::Result<f64> {
-        get_primitive2(self.tk_ptr, p, bindings::vrvToolkit_getTimeForElement)
+        get_primitive2(self.tk_ptr, p, bindings::vrvShim_getTimeForElement)
     }
 
     pub fn get_times_for_element(&self, p: impl AsRef<str>) -> anyhow::Result<ElementTime> {
-        let ret = get_string2(self.tk_ptr, p, bindings::vrvToolkit_getTimesForElement)
+        let ret = get_string2(self.tk_ptr, p, bindings::vrvShim_getTimesForElement)
             .map(|s| serde_json::from_str(&s))??;
         Ok(ret)
     }
-    pub fn get_version(&self) -> String {
-        get_string(self.tk_ptr, bindings::vrvToolkit_getVersion)
+    pub fn get_version(&self) -> anyhow::Result<String> {
+        get_string(self.tk_ptr, bindings::vrvShim_getVersion)
     }
 
     pub fn load_data(&self, p: impl AsRef<str>) -> anyhow::Result<bool> {
-        get_primitive2(self.tk_ptr, p, bindings::vrvToolkit_loadData)
+        self.invalidate_layout();
+        self.selection.take();
+        get_primitive2(self.tk_ptr, p, bindings::vrvShim_loadData)
     }
 
     pub fn load_file(&self, path: impl AsRef<Path>) -> anyhow::Result<bool> {
+        self.invalidate_layout();
+        self.selection.take();
         let path = path.as_ref().as_os_str().as_encoded_bytes();
         let path = CString::new(path)?;
-        unsafe { Ok(bindings::vrvToolkit_loadFile(self.tk_ptr, path.as_ptr())) }
+        let mut ret = false;
+        check(unsafe { bindings::vrvShim_loadFile(self.tk_ptr, path.as_ptr(), &mut ret) })?;
+        Ok(ret)
     }
 
     pub fn load_zip_data(&self, p: impl AsRef<[u8]>) -> anyhow::Result<bool> {
-        unsafe {
-            let p = p.as_ref();
-            Ok(bindings::vrvToolkit_loadZipDataBuffer(
+        self.invalidate_layout();
+        self.selection.take();
+        let p = p.as_ref();
+        let mut ret = false;
+        check(unsafe {
+            bindings::vrvShim_loadZipDataBuffer(
                 self.tk_ptr,
                 p.as_ptr() as _,
                 p.len() as _,
-            ))
-        }
+                &mut ret,
+            )
+        })?;
+        Ok(ret)
     }
 
     pub fn redo_layout(&self, p: impl AsRef<str>) -> anyhow::Result<()> {
-        get_primitive2(self.tk_ptr, p, bindings::vrvToolkit_redoLayout)
+        self.invalidate_layout();
+        let p = CString::new(p.as_ref())?;
+        check(unsafe { bindings::vrvShim_redoLayout(self.tk_ptr, p.as_ptr()) })
     }
 
-    pub fn redo_page_pitch_pos_layout(&self) {
-        unsafe {
-            bindings::vrvToolkit_redoPagePitchPosLayout(self.tk_ptr);
-        }
+    pub fn redo_page_pitch_pos_layout(&self) -> anyhow::Result<()> {
+        self.invalidate_layout();
+        check(unsafe { bindings::vrvShim_redoPagePitchPosLayout(self.tk_ptr) })
     }
 
     pub fn render_data(
@@ -213,56 +401,92 @@ impl VerovioToolkit {
         data: impl AsRef<str>,
         option: impl AsRef<str>,
     ) -> anyhow::Result<String> {
-        get_string3(self.tk_ptr, data, option, bindings::vrvToolkit_renderData)
+        get_string3(self.tk_ptr, data, option, bindings::vrvShim_renderData)
     }
 
-    pub fn render_to_expansion_map(&self) -> String {
-        get_string(self.tk_ptr, bindings::vrvToolkit_renderToExpansionMap)
+    pub fn render_to_expansion_map(&self) -> anyhow::Result<String> {
+        get_string(self.tk_ptr, bindings::vrvShim_renderToExpansionMap)
     }
 
     pub fn render_to_expansion_map_file(&self, p: impl AsRef<str>) -> anyhow::Result<bool> {
-        get_primitive2(
-            self.tk_ptr,
-            p,
-            bindings::vrvToolkit_renderToExpansionMapFile,
-        )
+        get_primitive2(self.tk_ptr, p, bindings::vrvShim_renderToExpansionMapFile)
     }
 
     pub fn render_to_midi(&self) -> anyhow::Result<Vec<u8>> {
-        let s = get_string(self.tk_ptr, bindings::vrvToolkit_renderToMIDI);
+        let s = get_string(self.tk_ptr, bindings::vrvShim_renderToMIDI)?;
         let ret = BASE64_STANDARD.decode(s.as_bytes())?;
         Ok(ret)
     }
 
     pub fn render_to_midi_file(&self, p: impl AsRef<str>) -> anyhow::Result<bool> {
-        get_primitive2(self.tk_ptr, p, bindings::vrvToolkit_renderToMIDIFile)
+        get_primitive2(self.tk_ptr, p, bindings::vrvShim_renderToMIDIFile)
     }
 
-    pub fn render_to_pae(&self) -> String {
-        get_string(self.tk_ptr, bindings::vrvToolkit_renderToPAE)
+    #[cfg(feature = "pae")]
+    pub fn render_to_pae(&self) -> anyhow::Result<String> {
+        get_string(self.tk_ptr, bindings::vrvShim_renderToPAE)
     }
 
+    #[cfg(feature = "pae")]
     pub fn render_to_pae_file(&self, p: impl AsRef<str>) -> anyhow::Result<bool> {
-        get_primitive2(self.tk_ptr, p, bindings::vrvToolkit_renderToPAEFile)
+        get_primitive2(self.tk_ptr, p, bindings::vrvShim_renderToPAEFile)
     }
 
-    pub fn render_to_svg(&self, page_no: i32, xml_declaration: bool) -> String {
-        unsafe {
-            let ret = bindings::vrvToolkit_renderToSVG(self.tk_ptr, page_no, xml_declaration);
-            CStr::from_ptr(ret).to_string_lossy().into_owned()
-        }
+    /// Render a page to SVG. Pages are numbered from 1.
+    pub fn render_to_svg(&self, page_no: i32, xml_declaration: bool) -> anyhow::Result<String> {
+        let svg = self.render_svg_buffer(page_no, xml_declaration)?;
+        Ok(unsafe { CStr::from_ptr(svg) }
+            .to_string_lossy()
+            .into_owned())
+    }
+
+    /// Render a page to SVG and write it to `writer`, without copying it into a `String`.
+    pub fn render_to_svg_writer(
+        &self,
+        page_no: i32,
+        xml_declaration: bool,
+        mut writer: impl Write,
+    ) -> anyhow::Result<()> {
+        let svg = self.render_svg_buffer(page_no, xml_declaration)?;
+        writer.write_all(unsafe { CStr::from_ptr(svg) }.to_bytes())?;
+        Ok(())
+    }
+
+    /// Render a page to SVG, borrowing the toolkit's own buffer instead of copying it.
+    ///
+    /// The buffer is reused by the next call into the toolkit, hence the `&mut self`.
+    pub fn render_to_svg_str(
+        &mut self,
+        page_no: i32,
+        xml_declaration: bool,
+    ) -> anyhow::Result<&str> {
+        let svg = self.render_svg_buffer(page_no, xml_declaration)?;
+        Ok(unsafe { CStr::from_ptr(svg) }.to_str()?)
+    }
+
+    /// Render a page into the toolkit's string buffer, valid until the next call.
+    fn render_svg_buffer(
+        &self,
+        page_no: i32,
+        xml_declaration: bool,
+    ) -> anyhow::Result<*const ::std::os::raw::c_char> {
+        self.check_page(page_no)?;
+        let mut ret = std::ptr::null();
+        check(unsafe {
+            bindings::vrvShim_renderToSVG(self.tk_ptr, page_no, xml_declaration, &mut ret)
+        })?;
+        Ok(ret)
     }
 
     pub fn render_to_svg_file(&self, path: impl AsRef<Path>, page_no: i32) -> anyhow::Result<bool> {
+        self.check_page(page_no)?;
         let path = path.as_ref().as_os_str().as_encoded_bytes();
         let path = CString::new(path)?;
-        unsafe {
-            Ok(bindings::vrvToolkit_renderToSVGFile(
-                self.tk_ptr,
-                path.as_ptr(),
-                page_no,
-            ))
-        }
+        let mut ret = false;
+        check(unsafe {
+            bindings::vrvShim_renderToSVGFile(self.tk_ptr, path.as_ptr(), page_no, &mut ret)
+        })?;
+        Ok(ret)
     }
 
     pub fn render_to_timemap(
@@ -274,7 +498,7 @@ impl VerovioToolkit {
         } else {
             "".into()
         };
-        get_string2(self.tk_ptr, &options, bindings::vrvToolkit_renderToTimemap)
+        get_string2(self.tk_ptr, &options, bindings::vrvShim_renderToTimemap)
     }
 
     pub fn render_to_timemap_file(
@@ -290,25 +514,25 @@ impl VerovioToolkit {
             vec![]
         };
         let options = CString::new(options)?;
-        unsafe {
-            Ok(bindings::vrvToolkit_renderToTimemapFile(
+        let mut ret = false;
+        check(unsafe {
+            bindings::vrvShim_renderToTimemapFile(
                 self.tk_ptr,
                 path.as_ptr(),
                 options.as_ptr(),
-            ))
-        }
+                &mut ret,
+            )
+        })?;
+        Ok(ret)
     }
 
-    pub fn reset_options(&self) {
-        unsafe {
-            bindings::vrvToolkit_resetOptions(self.tk_ptr);
-        }
+    pub fn reset_options(&self) -> anyhow::Result<()> {
+        self.invalidate_layout();
+        check(unsafe { bindings::vrvShim_resetOptions(self.tk_ptr) })
     }
 
-    pub fn reset_xml_seed(&self, seed: i32) {
-        unsafe {
-            bindings::vrvToolkit_resetXmlIdSeed(self.tk_ptr, seed);
-        }
+    pub fn reset_xml_seed(&self, seed: i32) -> anyhow::Result<()> {
+        check(unsafe { bindings::vrvShim_resetXmlIdSeed(self.tk_ptr, seed) })
     }
 
     pub fn save_file(
@@ -319,64 +543,126 @@ impl VerovioToolkit {
         let path = path.as_ref().as_os_str().as_encoded_bytes();
         let path = CString::new(path)?;
         let options = CString::new(options.as_ref())?;
-        unsafe {
-            Ok(bindings::vrvToolkit_saveFile(
-                self.tk_ptr,
-                path.as_ptr(),
-                options.as_ptr(),
-            ))
-        }
+        let mut ret = false;
+        check(unsafe {
+            bindings::vrvShim_saveFile(self.tk_ptr, path.as_ptr(), options.as_ptr(), &mut ret)
+        })?;
+        Ok(ret)
     }
 
     pub fn select(&self, select: impl AsRef<str>) -> anyhow::Result<bool> {
-        get_primitive2(self.tk_ptr, select, bindings::vrvToolkit_select)
+        self.invalidate_layout();
+        let select = select.as_ref();
+        let ret = get_primitive2(self.tk_ptr, select, bindings::vrvShim_select)?;
+        if ret {
+            let empty = select.trim().is_empty() || select.trim() == "{}";
+            *self.selection.borrow_mut() = (!empty).then(|| select.to_string());
+        }
+        Ok(ret)
     }
 
     pub fn set_input_from(&self, input: Format) -> anyhow::Result<bool> {
         let input: &str = input.into();
-        get_primitive2(self.tk_ptr, input, bindings::vrvToolkit_setInputFrom)
+        get_primitive2(self.tk_ptr, input, bindings::vrvShim_setInputFrom)
     }
 
     pub fn set_options(&self, options: &AllOptions) -> anyhow::Result<bool> {
-        let options = serde_json::to_string(options)?;
-        get_primitive2(self.tk_ptr, &options, bindings::vrvToolkit_setOptions)
+        self.set_options_value(serde_json::to_value(options)?)
+    }
+
+    /// All the options verovio has, including those [`AllOptions`] does not know about.
+    fn get_options_value(&self) -> anyhow::Result<serde_json::Value> {
+        let s = get_string(self.tk_ptr, bindings::vrvShim_getOptions)?;
+        Ok(serde_json::from_str(&s)?)
+    }
+
+    fn set_options_value(&self, options: serde_json::Value) -> anyhow::Result<bool> {
+        let changes_layout = options.as_object().is_none_or(|options| {
+            options
+                .iter()
+                .any(|(name, value)| !value.is_null() && !SVG_OPTIONS.contains(&name.as_str()))
+        });
+        if changes_layout {
+            self.invalidate_layout();
+        }
+        get_primitive2(
+            self.tk_ptr,
+            options.to_string(),
+            bindings::vrvShim_setOptions,
+        )
+    }
+
+    /// Forget what was computed from the current layout.
+    fn invalidate_layout(&self) {
+        self.page_index.take();
+    }
+
+    /// Run `f` with `options` set, then restore the options that were set before.
+    /// Only the options `options` sets are restored, so that setting SVG options
+    /// keeps what was computed from the layout.
+    fn with_options<T>(
+        &self,
+        options: &AllOptions,
+        f: impl FnOnce(&Self) -> anyhow::Result<T>,
+    ) -> anyhow::Result<T> {
+        let mut previous = serde_json::to_value(self.get_options()?)?;
+        if let (Some(previous), serde_json::Value::Object(options)) =
+            (previous.as_object_mut(), serde_json::to_value(options)?)
+        {
+            previous.retain(|name, _| options.get(name).is_some_and(|value| !value.is_null()));
+        }
+        let previous: AllOptions = serde_json::from_value(previous)?;
+        if !self.set_options(options)? {
+            return Err(anyhow::anyhow!("verovio rejected the options"));
+        }
+        let result = f(self);
+        self.set_options(&previous)?;
+        result
     }
 
     pub fn set_output_to(&self, format: Format) -> anyhow::Result<bool> {
         let format: &str = format.into();
-        get_primitive2(self.tk_ptr, format, bindings::vrvToolkit_setOutputTo)
+        get_primitive2(self.tk_ptr, format, bindings::vrvShim_setOutputTo)
     }
 
     pub fn set_resource_path(&self, path: impl AsRef<Path>) -> anyhow::Result<bool> {
+        self.invalidate_layout();
         let path = path.as_ref().as_os_str().as_encoded_bytes();
         let path = CString::new(path)?;
-        unsafe {
-            Ok(bindings::vrvToolkit_setResourcePath(
-                self.tk_ptr,
-                path.as_ptr(),
-            ))
-        }
+        let mut ret = false;
+        check(unsafe { bindings::vrvShim_setResourcePath(self.tk_ptr, path.as_ptr(), &mut ret) })?;
+        Ok(ret)
     }
 
-    pub fn set_scale(&self, scale: i32) -> bool {
-        unsafe { bindings::vrvToolkit_setScale(self.tk_ptr, scale) }
+    pub fn set_scale(&self, scale: i32) -> anyhow::Result<bool> {
+        self.invalidate_layout();
+        let mut ret = false;
+        check(unsafe { bindings::vrvShim_setScale(self.tk_ptr, scale, &mut ret) })?;
+        Ok(ret)
     }
 
+    #[cfg(feature = "pae")]
     pub fn validate_pae(&self, pae: impl AsRef<str>) -> anyhow::Result<String> {
-        get_string2(self.tk_ptr, pae, bindings::vrvToolkit_validatePAE)
+        get_string2(self.tk_ptr, pae, bindings::vrvShim_validatePAE)
     }
 
+    #[cfg(feature = "pae")]
     pub fn validate_pae_file(&self, path: impl AsRef<Path>) -> anyhow::Result<String> {
         let path = path.as_ref().as_os_str().as_encoded_bytes();
         let path = CString::new(path)?;
         unsafe {
-            let ret = bindings::vrvToolkit_validatePAEFile(self.tk_ptr, path.as_ptr());
+            let mut ret = std::ptr::null();
+            check(bindings::vrvShim_validatePAEFile(
+                self.tk_ptr,
+                path.as_ptr(),
+                &mut ret,
+            ))?;
             Ok(CStr::from_ptr(ret).to_string_lossy().into_owned())
         }
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ElementTime {
     #[serde(rename = "qfracOn")]
     pub score_time_onset: Vec<[i32; 2]>,
@@ -392,37 +678,50 @@ pub struct ElementTime {
     pub real_time_offset_milliseconds: Vec<f64>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Format {
+    #[cfg(feature = "abc")]
     Abc,
+    #[cfg(feature = "pae")]
     Pae,
     Darms,
     VolPiano,
     Cmme,
+    #[cfg(feature = "humdrum")]
     Humdrum,
     Mei,
     MusicXml,
+    #[cfg(feature = "humdrum")]
     MuseDataHum,
+    #[cfg(feature = "humdrum")]
     MeiHum,
     Esac,
     Serialization,
     Auto,
+    #[cfg(feature = "humdrum")]
     MusicxmlHum,
 }
 
 impl From<Format> for &'static str {
     fn from(input: Format) -> Self {
         match input {
+            #[cfg(feature = "abc")]
             Format::Abc => "abc",
+            #[cfg(feature = "pae")]
             Format::Pae => "pae",
             Format::Cmme => "cmme.xml",
+            #[cfg(feature = "humdrum")]
             Format::Humdrum => "humdrum",
             Format::Mei => "mei",
             Format::MusicXml => "musicxml",
+            #[cfg(feature = "humdrum")]
             Format::MuseDataHum => "musedata",
+            #[cfg(feature = "humdrum")]
             Format::MeiHum => "mei-hum",
             Format::Esac => "esac",
             Format::Serialization => "mei-pb-serialized",
             Format::Auto => "auto",
+            #[cfg(feature = "humdrum")]
             Format::MusicxmlHum => "musicxml-hum",
             Format::Darms => "darms",
             Format::VolPiano => "volpiano",
@@ -430,13 +729,91 @@ impl From<Format> for &'static str {
     }
 }
 
+/// A C++ exception that verovio threw and the shim caught at the FFI boundary.
+#[derive(Debug, Clone)]
+pub struct CppException {
+    pub message: String,
+}
+
+impl fmt::Display for CppException {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "verovio threw an exception: {}", self.message)
+    }
+}
+
+impl std::error::Error for CppException {}
+
+/// Files and directories of the resource path that verovio cannot work without.
+const REQUIRED_RESOURCES: &[&str] = &[
+    "Bravura.xml",
+    "Bravura",
+    "Leipzig.xml",
+    "Leipzig",
+    "text/Times.xml",
+];
+
+/// Why a toolkit could not be created with a resource path.
+#[derive(Debug)]
+pub enum ResourceError {
+    /// Required fonts or data files are missing from the resource path.
+    Missing { path: PathBuf, files: Vec<PathBuf> },
+    /// The files are present, but verovio failed to load the default font.
+    FontLoad { path: PathBuf },
+    /// Creating or configuring the toolkit failed, the cause being the error's source.
+    Toolkit(anyhow::Error),
+}
+
+impl fmt::Display for ResourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResourceError::Missing { path, files } => {
+                write!(f, "verovio resources in {} are missing ", path.display())?;
+                for (i, file) in files.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", file.display())?;
+                }
+                Ok(())
+            }
+            ResourceError::FontLoad { path } => {
+                write!(f, "verovio failed to load the fonts in {}", path.display())
+            }
+            ResourceError::Toolkit(_) => f.write_str("failed to set up the verovio toolkit"),
+        }
+    }
+}
+
+impl std::error::Error for ResourceError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ResourceError::Toolkit(err) => Some(err.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+fn check(status: ::std::os::raw::c_int) -> anyhow::Result<()> {
+    if status == bindings::VRV_SHIM_OK as ::std::os::raw::c_int {
+        return Ok(());
+    }
+    let message = unsafe { CStr::from_ptr(bindings::vrvShim_lastError()) }
+        .to_string_lossy()
+        .into_owned();
+    Err(CppException { message }.into())
+}
+
 fn get_string(
     p: *mut ::std::os::raw::c_void,
-    f: unsafe extern "C" fn(*mut ::std::os::raw::c_void) -> *const ::std::os::raw::c_char,
-) -> String {
+    f: unsafe extern "C" fn(
+        *mut ::std::os::raw::c_void,
+        *mut *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int,
+) -> anyhow::Result<String> {
     unsafe {
-        let r = f(p);
-        CStr::from_ptr(r).to_string_lossy().into_owned()
+        let mut r = std::ptr::null();
+        check(f(p, &mut r))?;
+        Ok(CStr::from_ptr(r).to_string_lossy().into_owned())
     }
 }
 
@@ -446,11 +823,13 @@ fn get_string2(
     f: unsafe extern "C" fn(
         *mut ::std::os::raw::c_void,
         *const ::std::os::raw::c_char,
-    ) -> *const ::std::os::raw::c_char,
+        *mut *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int,
 ) -> anyhow::Result<String> {
     unsafe {
         let p1 = CString::new(p1.as_ref())?;
-        let r = f(p, p1.as_ptr());
+        let mut r = std::ptr::null();
+        check(f(p, p1.as_ptr(), &mut r))?;
         Ok(CStr::from_ptr(r).to_string_lossy().into_owned())
     }
 }
@@ -463,28 +842,54 @@ fn get_string3(
         *mut ::std::os::raw::c_void,
         *const ::std::os::raw::c_char,
         *const ::std::os::raw::c_char,
-    ) -> *const ::std::os::raw::c_char,
+        *mut *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int,
 ) -> anyhow::Result<String> {
     unsafe {
         let p1 = CString::new(p1.as_ref())?;
         let p2 = CString::new(p2.as_ref())?;
-        let r = f(p, p1.as_ptr(), p2.as_ptr());
+        let mut r = std::ptr::null();
+        check(f(p, p1.as_ptr(), p2.as_ptr(), &mut r))?;
         Ok(CStr::from_ptr(r).to_string_lossy().into_owned())
     }
 }
 
-fn get_primitive2<T>(
+fn get_primitive<T: Default>(
+    p: *mut ::std::os::raw::c_void,
+    f: unsafe extern "C" fn(*mut ::std::os::raw::c_void, *mut T) -> ::std::os::raw::c_int,
+) -> anyhow::Result<T> {
+    let mut r = T::default();
+    check(unsafe { f(p, &mut r) })?;
+    Ok(r)
+}
+
+fn get_primitive2<T: Default>(
     p: *mut ::std::os::raw::c_void,
     p1: impl AsRef<str>,
-    f: unsafe extern "C" fn(*mut ::std::os::raw::c_void, *const ::std::os::raw::c_char) -> T,
+    f: unsafe extern "C" fn(
+        *mut ::std::os::raw::c_void,
+        *const ::std::os::raw::c_char,
+        *mut T,
+    ) -> ::std::os::raw::c_int,
 ) -> anyhow::Result<T> {
-    unsafe {
-        let p1 = CString::new(p1.as_ref())?;
-        Ok(f(p, p1.as_ptr()))
-    }
+    let p1 = CString::new(p1.as_ref())?;
+    let mut r = T::default();
+    check(unsafe { f(p, p1.as_ptr(), &mut r) })?;
+    Ok(r)
 }
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+/// Options that only change how the SVG of a page is written, not the layout.
+const SVG_OPTIONS: [&str; 7] = [
+    "svgAdditionalAttribute",
+    "svgBoundingBoxes",
+    "svgCss",
+    "svgFormatRaw",
+    "svgHtml5",
+    "svgRemoveXlink",
+    "svgViewBox",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct AllOptions {
     #[serde(rename = "inputFrom")]
     pub input_from: Option<String>,
@@ -816,7 +1221,7 @@ pub struct AllOptions {
     pub left_margin_meter_sig: Option<f64>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct TransposeMDiv {}
 
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -828,7 +1233,7 @@ pub struct Elements {
     pub rests: Vec<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct TimemapRenderOptions {
     #[serde(rename = "includeMeasures")]
     pub include_measures: bool,
@@ -0,0 +1,75 @@
+//! Where every element of the document is laid out.
+
+use crate::VerovioToolkit;
+use crate::geometry::has_class;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Where an element is laid out, as found by [`VerovioToolkit::build_page_index`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PageLocation {
+    /// The page number, starting at 1.
+    pub page: i32,
+    /// The index of the system on its page, starting at 0.
+    pub system: usize,
+    /// The id of the measure holding the element, `None` for the elements of a
+    /// system outside of its measures.
+    pub measure: Option<String>,
+}
+
+/// Locations by element id.
+pub type PageIndex = HashMap<String, PageLocation>;
+
+impl VerovioToolkit {
+    /// Locate every element drawn in the systems of the document.
+    ///
+    /// All pages are rendered the first time, the index is then kept until the layout may
+    /// change, that is until a document is loaded, the options, scale or resource path are
+    /// set, the layout is redone, or an edit or selection is made.
+    pub fn build_page_index(&self) -> anyhow::Result<Rc<PageIndex>> {
+        if let Some(index) = self.page_index.borrow().as_ref() {
+            return Ok(index.clone());
+        }
+        let mut index = PageIndex::new();
+        for page in self.pages()? {
+            let page = page?;
+            let doc = roxmltree::Document::parse(&page.svg)?;
+            let systems = doc
+                .descendants()
+                .filter(|n| n.has_tag_name("g") && has_class(*n, "system"));
+            for (system, node) in systems.enumerate() {
+                let location = PageLocation {
+                    page: page.number,
+                    system,
+                    measure: None,
+                };
+                collect(node, &location, &mut index);
+            }
+        }
+        let index = Rc::new(index);
+        *self.page_index.borrow_mut() = Some(index.clone());
+        Ok(index)
+    }
+}
+
+fn collect(node: roxmltree::Node, location: &PageLocation, index: &mut PageIndex) {
+    let id = node.attribute("id").or_else(|| node.attribute("data-id"));
+    let measure;
+    let location = match id {
+        Some(id) if has_class(node, "measure") => {
+            measure = PageLocation {
+                measure: Some(id.to_string()),
+                ..location.clone()
+            };
+            &measure
+        }
+        _ => location,
+    };
+    if let Some(id) = id {
+        index.insert(id.to_string(), location.clone());
+    }
+    for child in node.children().filter(|n| n.is_element()) {
+        collect(child, location, index);
+    }
+}
@@ -0,0 +1,206 @@
+//! Iterating over and writing out the rendered pages.
+
+use crate::{AllOptions, VerovioToolkit};
+use anyhow::{Context, anyhow};
+use std::fs;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+/// The `xmlIdSeed` used by [`VerovioToolkit::render_pages_parallel`] when the options have none,
+/// since every toolkit has to generate the same element ids.
+const PARALLEL_XML_ID_SEED: i32 = 1;
+
+/// A page rendered to SVG.
+#[derive(Debug, Clone)]
+pub struct Page {
+    /// The page number, starting at 1.
+    pub number: i32,
+    pub svg: String,
+}
+
+/// Iterator over the pages of a document, returned by [`VerovioToolkit::pages`].
+pub struct Pages<'a> {
+    toolkit: &'a VerovioToolkit,
+    next: i32,
+    count: i32,
+}
+
+impl Iterator for Pages<'_> {
+    type Item = anyhow::Result<Page>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next > self.count {
+            return None;
+        }
+        let number = self.next;
+        self.next += 1;
+        Some(
+            self.toolkit
+                .render_to_svg(number, false)
+                .map(|svg| Page { number, svg }),
+        )
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = (self.count - self.next + 1).max(0) as usize;
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for Pages<'_> {}
+
+impl VerovioToolkit {
+    /// Iterate over the pages of the loaded document, rendered to SVG.
+    ///
+    /// The page count is taken when the iterator is created.
+    pub fn pages(&self) -> anyhow::Result<Pages<'_>> {
+        Ok(Pages {
+            toolkit: self,
+            next: 1,
+            count: self.get_page_count()?,
+        })
+    }
+
+    /// Render every page to an SVG file in `dir` and return the paths written.
+    ///
+    /// The file names come from `pattern`, whose run of `#` is replaced with the
+    /// zero-padded page number, so `score-###.svg` gives `score-001.svg` and so on.
+    /// The number is never truncated, a document with more pages than the run can
+    /// hold gets longer names.
+    pub fn render_all_to_dir(
+        &self,
+        dir: impl AsRef<Path>,
+        pattern: &str,
+    ) -> anyhow::Result<Vec<PathBuf>> {
+        let start = pattern
+            .find('#')
+            .ok_or_else(|| anyhow!("pattern {} has no # for the page number", pattern))?;
+        let width = pattern[start..]
+            .find(|c| c != '#')
+            .unwrap_or(pattern.len() - start);
+        if pattern[start + width..].contains('#') {
+            return Err(anyhow!("pattern {} has more than one run of #", pattern));
+        }
+        let (prefix, suffix) = (&pattern[..start], &pattern[start + width..]);
+
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        let mut paths = vec![];
+        for page in self.pages()? {
+            let page = page?;
+            let path = dir.join(format!(
+                "{}{:0width$}{}",
+                prefix,
+                page.number,
+                suffix,
+                width = width
+            ));
+            fs::write(&path, page.svg)?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+
+    /// Load `input` with `options` and render all its pages on up to `threads` threads.
+    ///
+    /// `options` are set on this toolkit on top of those it already has, and the
+    /// document is loaded and its first pages rendered here, so that it stays loaded
+    /// afterwards. Every other thread creates a toolkit with the same resource path,
+    /// options and fonts added with [`VerovioToolkit::add_font`], loads the document and
+    /// renders a contiguous range of pages. Every toolkit, this one included, is seeded
+    /// with the `xml_id_seed` of `options`, else of this toolkit, else a fixed seed, so
+    /// that all of them give the elements the same ids. The pages are returned in order
+    /// and are identical to rendering them one by one on this toolkit.
+    pub fn render_pages_parallel(
+        &self,
+        input: &str,
+        options: &AllOptions,
+        threads: usize,
+    ) -> anyhow::Result<Vec<Page>> {
+        let mut options = options.clone();
+        // Set even when this toolkit has a seed, for it to be reseeded for this load as
+        // the other toolkits are.
+        options.xml_id_seed = Some(match options.xml_id_seed {
+            Some(seed) => seed,
+            None => match self.get_options()?.xml_id_seed {
+                Some(seed) if seed != 0 => seed,
+                _ => PARALLEL_XML_ID_SEED,
+            },
+        });
+        self.load_with_options(input, &options)?;
+        let count = self.get_page_count()?;
+        let resource_path = self.get_resource_path()?;
+        // Everything the document was loaded with, including the scale and the custom fonts,
+        // whose files live as long as this toolkit.
+        let all_options = self.get_options_value()?;
+
+        let chunk = (count.max(1) as usize).div_ceil(threads.max(1)) as i32;
+        let ranges: Vec<RangeInclusive<i32>> = (0..count)
+            .step_by(chunk as usize)
+            .map(|start| start + 1..=(start + chunk).min(count))
+            .collect();
+
+        thread::scope(|scope| {
+            let workers: Vec<_> = ranges
+                .iter()
+                .skip(1)
+                .map(|range| {
+                    let (all_options, resource_path) = (&all_options, &resource_path);
+                    scope.spawn(move || -> anyhow::Result<Vec<Page>> {
+                        let toolkit =
+                            VerovioToolkit::with_resource(resource_path).with_context(|| {
+                                format!(
+                                    "render thread could not create a toolkit with the resources in {}",
+                                    resource_path
+                                )
+                            })?;
+                        if !toolkit.set_options_value(all_options.clone())? {
+                            return Err(anyhow!("render thread could not set the options"));
+                        }
+                        if !toolkit.load_data(input)? {
+                            return Err(anyhow!("render thread could not load the document"));
+                        }
+                        if toolkit.get_page_count()? != count {
+                            return Err(anyhow!(
+                                "the document was laid out differently on another thread"
+                            ));
+                        }
+                        toolkit.render_range(range.clone())
+                    })
+                })
+                .collect();
+
+            let mut pages = match ranges.first() {
+                Some(range) => self.render_range(range.clone())?,
+                None => vec![],
+            };
+            for worker in workers {
+                let rendered = worker
+                    .join()
+                    .map_err(|_| anyhow!("render thread panicked"))??;
+                pages.extend(rendered);
+            }
+            Ok(pages)
+        })
+    }
+
+    fn load_with_options(&self, input: &str, options: &AllOptions) -> anyhow::Result<()> {
+        if !self.set_options(options)? {
+            return Err(anyhow!("verovio rejected the options"));
+        }
+        if !self.load_data(input)? {
+            return Err(anyhow!("verovio could not load the document"));
+        }
+        Ok(())
+    }
+
+    fn render_range(&self, range: RangeInclusive<i32>) -> anyhow::Result<Vec<Page>> {
+        range
+            .map(|number| {
+                self.render_to_svg(number, false)
+                    .map(|svg| Page { number, svg })
+            })
+            .collect()
+    }
+}
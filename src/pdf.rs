@@ -0,0 +1,162 @@
+//! Rendering documents to PDF with svg2pdf.
+
+use crate::pdf_text::{self, Fonts};
+use crate::{Coloring, VerovioToolkit, svg_tree};
+use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref, TextStr};
+use std::fs;
+use std::path::Path;
+use usvg::Transform;
+use usvg::utils::view_box_to_transform;
+
+/// Points per verovio unit, which is a tenth of a millimetre.
+const POINTS_PER_UNIT: f32 = 72.0 / 254.0;
+
+/// How documents are rendered to PDF.
+#[derive(Debug, Clone, Default)]
+pub struct PdfOptions {
+    /// Colour the staves, layers or parts.
+    pub coloring: Option<Coloring>,
+}
+
+impl VerovioToolkit {
+    /// Render every page of the loaded document to a vector PDF.
+    ///
+    /// Every page has the size of its SVG, so the `page_width`, `page_height` and
+    /// `landscape` options apply, as do `adjust_page_width` and `adjust_page_height`,
+    /// and the music is never distorted. Text is written with subsets of the TrueType
+    /// fonts of the resource path and of the system embedded, so that it can be
+    /// searched; text that plain glyphs cannot reproduce, such as text on a path, right
+    /// to left, in a CFF font, clipped or drawn over, is drawn as outlines. The title
+    /// and composer of the MEI header become the title and author of the PDF.
+    pub fn render_to_pdf(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        self.render_to_pdf_with(path, &PdfOptions::default())
+    }
+
+    /// Render every page of the loaded document to a vector PDF, as [`VerovioToolkit::render_to_pdf`].
+    pub fn render_to_pdf_with(
+        &self,
+        path: impl AsRef<Path>,
+        pdf_options: &PdfOptions,
+    ) -> anyhow::Result<()> {
+        let scale = self.get_scale()?;
+        let resource_path = self.get_resource_path()?;
+        let resource_path = Path::new(&resource_path);
+        // Points per SVG pixel, the same both ways so that the music keeps its proportions.
+        let points_per_px = svg_tree::units_per_svg_px(scale) * POINTS_PER_UNIT;
+        let mut fonts = Fonts::new(svg_tree::fonts(resource_path));
+
+        let mut pdf = Pdf::new();
+        let catalog_ref = Ref::new(1);
+        let page_tree_ref = Ref::new(2);
+        let info_ref = Ref::new(3);
+        let mut next_ref = Ref::new(4);
+        let mut page_refs = vec![];
+        for page_no in 1..=self.get_page_count()? {
+            let svg = self.render_to_svg_for_output(page_no, pdf_options.coloring.as_ref())?;
+            let mut tree = svg_tree::parse(&svg, resource_path)?;
+            let width = tree.size.width() * points_per_px;
+            let height = tree.size.height() * points_per_px;
+
+            let page_ref = next_ref;
+            let content_ref = Ref::new(page_ref.get() + 1);
+            next_ref = Ref::new(page_ref.get() + 2);
+            // From the user space of the SVG to the page, whose y axis goes up.
+            let page = Transform::from_row(points_per_px, 0.0, 0.0, -points_per_px, 0.0, height)
+                .pre_concat(view_box_to_transform(
+                    tree.view_box.rect,
+                    tree.view_box.aspect,
+                    tree.size,
+                ));
+            let text = fonts.take_text(&mut tree.root, page, &mut next_ref);
+            let svg_ref = next_ref;
+            next_ref =
+                svg2pdf::convert_tree_into(&tree, svg2pdf::Options::default(), &mut pdf, svg_ref);
+            page_refs.push(page_ref);
+
+            let svg_name = Name(b"S1");
+            let mut pdf_page = pdf.page(page_ref);
+            pdf_page
+                .media_box(Rect::new(0.0, 0.0, width, height))
+                .parent(page_tree_ref)
+                .contents(content_ref);
+            let mut resources = pdf_page.resources();
+            resources.x_objects().pair(svg_name, svg_ref);
+            fonts.resources(&text, &mut resources);
+            resources.finish();
+            pdf_page.finish();
+
+            let mut content = Content::new();
+            content
+                .save_state()
+                .transform([width, 0.0, 0.0, height, 0.0, 0.0])
+                .x_object(svg_name)
+                .restore_state();
+            pdf_text::draw(&text, &mut content);
+            pdf.stream(content_ref, &content.finish());
+        }
+        fonts.write(&mut pdf)?;
+
+        pdf.catalog(catalog_ref).pages(page_tree_ref);
+        pdf.pages(page_tree_ref)
+            .count(page_refs.len() as i32)
+            .kids(page_refs);
+        let mei = self.get_mei("")?;
+        let header = MeiHeader::parse(&mei)?;
+        let mut info = pdf.document_info(info_ref);
+        info.producer(TextStr("verovio"));
+        if let Some(title) = &header.title {
+            info.title(TextStr(title));
+        }
+        if let Some(composer) = &header.composer {
+            info.author(TextStr(composer));
+        }
+        info.finish();
+
+        fs::write(path, pdf.finish())?;
+        Ok(())
+    }
+}
+
+/// The parts of an MEI header that go into the PDF metadata.
+struct MeiHeader {
+    title: Option<String>,
+    composer: Option<String>,
+}
+
+impl MeiHeader {
+    fn parse(mei: &str) -> anyhow::Result<MeiHeader> {
+        let doc = roxmltree::Document::parse(mei)?;
+        let Some(title_stmt) = doc
+            .descendants()
+            .find(|n| n.has_tag_name("meiHead"))
+            .and_then(|head| head.descendants().find(|n| n.has_tag_name("titleStmt")))
+        else {
+            return Ok(MeiHeader {
+                title: None,
+                composer: None,
+            });
+        };
+        let text = |node: roxmltree::Node| {
+            let text: String = node
+                .descendants()
+                .filter_map(|n| n.text())
+                .collect::<Vec<_>>()
+                .join(" ");
+            let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+            (!text.is_empty()).then_some(text)
+        };
+        let title = title_stmt
+            .children()
+            .filter(|n| n.has_tag_name("title"))
+            .find_map(text);
+        // MEI 5 has a composer element, earlier versions a persName in a respStmt.
+        let composer = title_stmt
+            .descendants()
+            .filter(|n| {
+                n.has_tag_name("composer")
+                    || (n.has_tag_name("persName") && n.attribute("role") == Some("composer"))
+            })
+            .find_map(text);
+        Ok(MeiHeader { title, composer })
+    }
+}
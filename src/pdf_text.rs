@@ -0,0 +1,986 @@
+//! Text of the PDF pages, written with embedded fonts so that it can be searched and copied.
+//!
+//! svg2pdf only draws text as outlines. Every text that can be reproduced with plain
+//! glyph positioning is removed from the tree before conversion and drawn on top of
+//! the page instead, shaped with rustybuzz as usvg shapes it. The rest stays outlined:
+//! text on a path, with strokes, under clip paths that are not rectangles around it or
+//! group effects, with something drawn over it, written right to left, or in a font
+//! that cannot be subset.
+
+use crate::font_subset;
+use miniz_oxide::deflate::compress_to_vec_zlib;
+use pdf_writer::types::{CidFontType, FontFlags, SystemInfo, UnicodeCmap};
+use pdf_writer::{Content, Filter, Finish, Name, Pdf, Rect, Ref, Str};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+use std::sync::Arc;
+use unicode_bidi::BidiClass;
+use usvg::{
+    AlignmentBaseline, BaselineShift, DominantBaseline, FontStretch, FontStyle, Node, NonZeroRect,
+    Paint, TextAnchor, TextFlow, Transform, Units, Visibility, WritingMode, fontdb,
+};
+
+const SYSTEM_INFO: SystemInfo = SystemInfo {
+    registry: Str(b"Adobe"),
+    ordering: Str(b"Identity"),
+    supplement: 0,
+};
+
+/// A run of glyphs of one font, size and colour on a baseline.
+pub(crate) struct TextRun {
+    font: usize,
+    size: f32,
+    color: [f32; 3],
+    /// Maps the run's text space, starting at its first glyph, to the page.
+    matrix: Transform,
+    /// Glyph ids and the `TJ` adjustment before each of them.
+    glyphs: Vec<(u16, f32)>,
+}
+
+/// A glyph laid out in a text chunk.
+struct Placed {
+    font: usize,
+    glyph: u16,
+    /// Advance of the glyph in the font, in em, which the PDF viewer moves by.
+    advance: f32,
+    x: f32,
+    y: f32,
+    size: f32,
+    color: Option<[f32; 3]>,
+}
+
+/// A glyph shaped by rustybuzz, its advance and offsets in em.
+struct Shaped {
+    glyph: u16,
+    /// Byte offset of the first character of the glyph's cluster in the shaped text.
+    cluster: usize,
+    x_advance: f32,
+    x_offset: f32,
+    y_offset: f32,
+    /// Advance of the glyph in the font, before kerning.
+    advance: f32,
+}
+
+/// A font file and the index of a face in it.
+type FaceData = Arc<(Vec<u8>, u32)>;
+
+struct Font {
+    id: fontdb::ID,
+    data: FaceData,
+    refs: Ref,
+    /// Width in thousandths of an em and the text of every glyph used.
+    glyphs: BTreeMap<u16, (f32, String)>,
+}
+
+/// The fonts used by the text of a document, embedded as compressed subsets.
+pub(crate) struct Fonts {
+    db: Arc<fontdb::Database>,
+    fonts: Vec<Font>,
+    /// The embedded font of every face, `None` for faces that cannot be embedded.
+    embedded: HashMap<fontdb::ID, Option<usize>>,
+    data: HashMap<fontdb::ID, Option<FaceData>>,
+    has_char: HashMap<(fontdb::ID, char), bool>,
+    fallbacks: HashMap<(char, u16, bool), Option<fontdb::ID>>,
+}
+
+impl Fonts {
+    pub(crate) fn new(db: Arc<fontdb::Database>) -> Fonts {
+        Fonts {
+            db,
+            fonts: vec![],
+            embedded: HashMap::new(),
+            data: HashMap::new(),
+            has_char: HashMap::new(),
+            fallbacks: HashMap::new(),
+        }
+    }
+
+    /// Take the text out of `root` where it can be drawn with embedded fonts, returning
+    /// it laid out on the page that `page` maps the SVG onto. New fonts take their five
+    /// objects from `next_ref`.
+    pub(crate) fn take_text(
+        &mut self,
+        root: &mut usvg::Group,
+        page: Transform,
+        next_ref: &mut Ref,
+    ) -> Vec<TextRun> {
+        let mut boxes = vec![];
+        drawn_boxes(root, &mut boxes);
+        let mut taker = Taker {
+            page,
+            boxes,
+            drawn: 0,
+            runs: vec![],
+        };
+        taker.take(self, root, Some(None), next_ref);
+        taker.runs
+    }
+
+    /// Lay `text` out with the embedded fonts, `None` if only outlines can show it.
+    fn layout(
+        &mut self,
+        text: &usvg::Text,
+        page: Transform,
+        next_ref: &mut Ref,
+    ) -> Option<Vec<TextRun>> {
+        if text.writing_mode != WritingMode::LeftToRight
+            || text.rotate.iter().any(|r| *r != 0.0)
+            || text
+                .chunks
+                .iter()
+                .any(|chunk| chunk.text.chars().any(is_rtl))
+        {
+            return None;
+        }
+        let transform = page.pre_concat(text.abs_transform);
+        let mut runs = vec![];
+        // The glyphs to add to the fonts, once the whole text can be drawn.
+        let mut used = vec![];
+        let (mut x, mut y) = (0.0, 0.0);
+        // Index of the first character of the chunk in the text, for `dx` and `dy`.
+        let mut first_char = 0;
+        for chunk in &text.chunks {
+            if !matches!(chunk.text_flow, TextFlow::Linear) {
+                return None;
+            }
+            x = chunk.x.unwrap_or(x);
+            y = chunk.y.unwrap_or(y);
+            let chars: Vec<usize> = chunk.text.char_indices().map(|(byte, _)| byte).collect();
+            let char_index = |byte: usize| first_char + chars.partition_point(|b| *b < byte);
+
+            // Runs of characters in one span and face, shaped together.
+            let mut segments: Vec<(&usvg::TextSpan, fontdb::ID, Range<usize>)> = vec![];
+            for (byte, c) in chunk.text.char_indices() {
+                let span = chunk
+                    .spans
+                    .iter()
+                    .find(|s| s.start <= byte && byte < s.end)?;
+                if !is_plain(span) {
+                    return None;
+                }
+                let id = self.face(&span.font, c)?;
+                match segments.last_mut() {
+                    Some((s, i, range)) if std::ptr::eq(*s, span) && *i == id => {
+                        range.end = byte + c.len_utf8()
+                    }
+                    _ => segments.push((span, id, byte..byte + c.len_utf8())),
+                }
+            }
+
+            let mut placed = vec![];
+            let (mut pen, mut rise) = (0.0, 0.0);
+            for (span, id, range) in segments {
+                let color = match &span.fill {
+                    Some(fill) if fill.opacity.get() != 1.0 => return None,
+                    Some(usvg::Fill {
+                        paint: Paint::Color(c),
+                        ..
+                    }) => Some([c.red, c.green, c.blue].map(|v| v as f32 / 255.0)),
+                    Some(_) => return None,
+                    None => None,
+                };
+                let size = span.font_size.get();
+                let font = self.embed(id, next_ref)?;
+                let shaped = self.shape(id, &chunk.text[range.clone()])?;
+                let mut clusters: Vec<usize> = shaped.iter().map(|g| g.cluster).collect();
+                clusters.sort_unstable();
+                clusters.dedup();
+                for (i, glyph) in shaped.iter().enumerate() {
+                    let start = range.start + glyph.cluster;
+                    let new_cluster = i == 0 || shaped[i - 1].cluster != glyph.cluster;
+                    if new_cluster {
+                        if i > 0 {
+                            pen += span.letter_spacing;
+                        }
+                        pen += text.dx.get(char_index(start)).copied().unwrap_or(0.0);
+                        rise += text.dy.get(char_index(start)).copied().unwrap_or(0.0);
+                    }
+                    let end = clusters
+                        .iter()
+                        .find(|c| **c > glyph.cluster)
+                        .map_or(range.end, |c| range.start + c);
+                    let cluster_text = &chunk.text[start..end];
+                    if new_cluster && cluster_text.chars().all(is_word_separator) {
+                        pen += span.word_spacing;
+                    }
+                    let text = if new_cluster { cluster_text } else { "" };
+                    used.push((font, glyph.glyph, glyph.advance, text.to_string()));
+                    placed.push(Placed {
+                        font,
+                        glyph: glyph.glyph,
+                        advance: glyph.advance,
+                        x: pen + glyph.x_offset * size,
+                        y: rise - glyph.y_offset * size,
+                        size,
+                        color: color.filter(|_| span.visibility == Visibility::Visible),
+                    });
+                    pen += glyph.x_advance * size;
+                }
+            }
+            let shift = match chunk.anchor {
+                TextAnchor::Start => 0.0,
+                TextAnchor::Middle => pen / 2.0,
+                TextAnchor::End => pen,
+            };
+            runs.extend(runs_of(&placed, transform, x - shift, y));
+            x += pen - shift;
+            y += rise;
+            first_char += chars.len();
+        }
+        for (font, glyph, advance, text) in used {
+            let entry = self.fonts[font]
+                .glyphs
+                .entry(glyph)
+                .or_insert((advance * 1000.0, String::new()));
+            if entry.1.is_empty() {
+                entry.1 = text;
+            }
+        }
+        Some(runs)
+    }
+
+    /// The face drawing `c` in `font`: the face `font` asks for if it has the
+    /// character, else another face with it.
+    fn face(&mut self, font: &usvg::Font, c: char) -> Option<fontdb::ID> {
+        match self.query(font) {
+            Some(id) if self.has_char(id, c) => Some(id),
+            _ => self.fallback(font, c),
+        }
+    }
+
+    fn query(&self, font: &usvg::Font) -> Option<fontdb::ID> {
+        let mut families: Vec<fontdb::Family> = font
+            .families
+            .iter()
+            .map(|family| match family.as_str() {
+                "serif" => fontdb::Family::Serif,
+                "sans-serif" => fontdb::Family::SansSerif,
+                "monospace" => fontdb::Family::Monospace,
+                "cursive" => fontdb::Family::Cursive,
+                "fantasy" => fontdb::Family::Fantasy,
+                name => fontdb::Family::Name(name),
+            })
+            .collect();
+        families.push(fontdb::Family::Serif);
+        self.db.query(&fontdb::Query {
+            families: &families,
+            weight: fontdb::Weight(font.weight),
+            stretch: stretch(font.stretch),
+            style: match font.style {
+                FontStyle::Normal => fontdb::Style::Normal,
+                FontStyle::Italic => fontdb::Style::Italic,
+                FontStyle::Oblique => fontdb::Style::Oblique,
+            },
+        })
+    }
+
+    /// The data of a face, read once.
+    fn face_data(&mut self, id: fontdb::ID) -> Option<FaceData> {
+        self.data
+            .entry(id)
+            .or_insert_with(|| {
+                self.db
+                    .with_face_data(id, |data, index| Arc::new((data.to_vec(), index)))
+            })
+            .clone()
+    }
+
+    fn has_char(&mut self, id: fontdb::ID, c: char) -> bool {
+        if let Some(has) = self.has_char.get(&(id, c)) {
+            return *has;
+        }
+        let has = self.face_data(id).is_some_and(|data| {
+            ttf_parser::Face::parse(&data.0, data.1).is_ok_and(|face| face.glyph_index(c).is_some())
+        });
+        self.has_char.insert((id, c), has);
+        has
+    }
+
+    /// Shape `text` in a face, `None` if a glyph is missing.
+    fn shape(&mut self, id: fontdb::ID, text: &str) -> Option<Vec<Shaped>> {
+        let data = self.face_data(id)?;
+        let face = rustybuzz::Face::from_slice(&data.0, data.1)?;
+        let mut buffer = rustybuzz::UnicodeBuffer::new();
+        buffer.push_str(text);
+        buffer.guess_segment_properties();
+        buffer.set_direction(rustybuzz::Direction::LeftToRight);
+        let output = rustybuzz::shape(&face, &[], buffer);
+        let em = face.units_per_em() as f32;
+        output
+            .glyph_infos()
+            .iter()
+            .zip(output.glyph_positions())
+            .map(|(info, position)| {
+                let glyph = u16::try_from(info.glyph_id).ok().filter(|g| *g != 0)?;
+                let advance = face.glyph_hor_advance(ttf_parser::GlyphId(glyph))?;
+                Some(Shaped {
+                    glyph,
+                    cluster: info.cluster as usize,
+                    x_advance: position.x_advance as f32 / em,
+                    x_offset: position.x_offset as f32 / em,
+                    y_offset: position.y_offset as f32 / em,
+                    advance: advance as f32 / em,
+                })
+            })
+            .collect()
+    }
+
+    /// The face with `c` closest in style and weight to `font`.
+    fn fallback(&mut self, font: &usvg::Font, c: char) -> Option<fontdb::ID> {
+        let italic = font.style != FontStyle::Normal;
+        let key = (c, font.weight, italic);
+        if let Some(id) = self.fallbacks.get(&key) {
+            return *id;
+        }
+        let mut faces: Vec<(bool, u16, fontdb::ID)> = self
+            .db
+            .faces()
+            .map(|face| {
+                let style = (face.style != fontdb::Style::Normal) != italic;
+                (style, face.weight.0.abs_diff(font.weight), face.id)
+            })
+            .collect();
+        faces.sort_unstable_by_key(|(style, weight, _)| (*style, *weight));
+        let id = faces
+            .into_iter()
+            .map(|(_, _, id)| id)
+            .find(|id| self.has_char(*id, c));
+        self.fallbacks.insert(key, id);
+        id
+    }
+
+    /// The index of the embedded font of a face, `None` for the faces that cannot be
+    /// subset, which have no TrueType outlines.
+    fn embed(&mut self, id: fontdb::ID, next_ref: &mut Ref) -> Option<usize> {
+        if let Some(index) = self.embedded.get(&id) {
+            return *index;
+        }
+        let data = self.face_data(id).filter(|data| {
+            ttf_parser::Face::parse(&data.0, data.1).is_ok_and(|face| {
+                let tables = face.tables();
+                tables.glyf.is_some() && tables.cff.is_none()
+            })
+        });
+        let index = data.map(|data| {
+            self.fonts.push(Font {
+                id,
+                data,
+                refs: *next_ref,
+                glyphs: BTreeMap::new(),
+            });
+            *next_ref = Ref::new(next_ref.get() + 5);
+            self.fonts.len() - 1
+        });
+        self.embedded.insert(id, index);
+        index
+    }
+
+    /// Add the fonts drawing `runs` to the resources of a page.
+    pub(crate) fn resources(
+        &self,
+        runs: &[TextRun],
+        resources: &mut pdf_writer::writers::Resources,
+    ) {
+        let mut used: Vec<usize> = runs.iter().map(|run| run.font).collect();
+        used.sort_unstable();
+        used.dedup();
+        let mut fonts = resources.fonts();
+        for index in used {
+            fonts.pair(Name(font_name(index).as_bytes()), self.fonts[index].refs);
+        }
+    }
+
+    /// Write the fonts used by the pages, subset to the glyphs they draw.
+    pub(crate) fn write(&self, pdf: &mut Pdf) -> anyhow::Result<()> {
+        for (index, font) in self.fonts.iter().enumerate() {
+            // A font whose text was outlined after all.
+            if font.glyphs.is_empty() {
+                continue;
+            }
+            let (data, face_index) = &*font.data;
+            let face = ttf_parser::Face::parse(data, *face_index)?;
+            let base_font: String = self
+                .db
+                .face(font.id)
+                .map(|face| face.post_script_name.as_str())
+                .unwrap_or_default()
+                .chars()
+                .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+                .collect();
+            // The tag marking a subset, derived from the glyphs so that it is stable.
+            let tag: String = font
+                .glyphs
+                .keys()
+                .fold(index as u32, |tag, glyph| {
+                    tag.wrapping_mul(31).wrapping_add(*glyph as u32)
+                })
+                .to_be_bytes()
+                .iter()
+                .chain(&[index as u8, font.glyphs.len() as u8])
+                .map(|b| (b'A' + b % 26) as char)
+                .collect();
+            let base_font = if base_font.is_empty() {
+                format!("{}+{}", tag, font_name(index))
+            } else {
+                format!("{}+{}", tag, base_font)
+            };
+            let [type0_ref, cid_ref, descriptor_ref, file_ref, cmap_ref] =
+                [0, 1, 2, 3, 4].map(|i| Ref::new(font.refs.get() + i));
+
+            pdf.type0_font(type0_ref)
+                .base_font(Name(base_font.as_bytes()))
+                .encoding_predefined(Name(b"Identity-H"))
+                .descendant_font(cid_ref)
+                .to_unicode(cmap_ref);
+
+            let mut cid = pdf.cid_font(cid_ref);
+            cid.subtype(CidFontType::Type2)
+                .base_font(Name(base_font.as_bytes()))
+                .system_info(SYSTEM_INFO)
+                .font_descriptor(descriptor_ref)
+                .default_width(0.0)
+                .cid_to_gid_map_predefined(Name(b"Identity"));
+            let mut widths = cid.widths();
+            for (glyph, (width, _)) in &font.glyphs {
+                widths.consecutive(*glyph, [*width]);
+            }
+            widths.finish();
+            cid.finish();
+
+            let em = 1000.0 / face.units_per_em() as f32;
+            let bbox = face.global_bounding_box();
+            let mut descriptor = pdf.font_descriptor(descriptor_ref);
+            descriptor
+                .name(Name(base_font.as_bytes()))
+                .flags(flags(&face))
+                .bbox(Rect::new(
+                    bbox.x_min as f32 * em,
+                    bbox.y_min as f32 * em,
+                    bbox.x_max as f32 * em,
+                    bbox.y_max as f32 * em,
+                ))
+                .italic_angle(face.italic_angle().unwrap_or(0.0))
+                .ascent(face.ascender() as f32 * em)
+                .descent(face.descender() as f32 * em)
+                .cap_height(face.capital_height().unwrap_or(face.ascender()) as f32 * em)
+                .stem_v(80.0)
+                .font_file2(file_ref);
+            descriptor.finish();
+
+            let subset = font_subset::subset(data, *face_index, font.glyphs.keys().copied())?;
+            let compressed = compress_to_vec_zlib(&subset, 6);
+            let mut file = pdf.stream(file_ref, &compressed);
+            file.filter(Filter::FlateDecode);
+            file.pair(Name(b"Length1"), subset.len() as i32);
+            file.finish();
+
+            let mut cmap = UnicodeCmap::new(Name(b"Custom"), SYSTEM_INFO);
+            for (glyph, (_, text)) in &font.glyphs {
+                if !text.is_empty() {
+                    cmap.pair_with_multiple(*glyph, text.chars());
+                }
+            }
+            pdf.cmap(cmap_ref, &compress_to_vec_zlib(&cmap.finish(), 6))
+                .filter(Filter::FlateDecode);
+        }
+        Ok(())
+    }
+}
+
+/// Walks the tree in paint order, taking out the text that can be drawn on top of it.
+struct Taker {
+    page: Transform,
+    /// The bounding box of everything drawn, in paint order, in SVG user space.
+    boxes: Vec<Option<NonZeroRect>>,
+    /// How many of `boxes` have been walked past.
+    drawn: usize,
+    runs: Vec<TextRun>,
+}
+
+impl Taker {
+    /// Take the text out of `group`. `clip` is the rectangle the clip paths of the
+    /// ancestors leave visible, `None` when they are not rectangles and nothing can be
+    /// taken out.
+    fn take(
+        &mut self,
+        fonts: &mut Fonts,
+        group: &mut usvg::Group,
+        clip: Option<Option<NonZeroRect>>,
+        next_ref: &mut Ref,
+    ) {
+        let clip = clip.and_then(|outer| {
+            let Some(clip_path) = &group.clip_path else {
+                return Some(outer);
+            };
+            let inner = clip_rect(&clip_path.borrow(), group.abs_transform)?;
+            match outer {
+                Some(outer) => intersect(&outer, &inner).map(Some),
+                None => Some(Some(inner)),
+            }
+        });
+        let plain = group.opacity.get() == 1.0 && group.mask.is_none() && group.filters.is_empty();
+        let clip = clip.filter(|_| plain);
+        for node in &mut group.children {
+            match node {
+                Node::Group(group) => self.take(fonts, group, clip, next_ref),
+                Node::Text(text) => {
+                    let index = self.drawn;
+                    self.drawn += 1;
+                    let Some(clip) = clip else {
+                        continue;
+                    };
+                    let Some(bbox) = self.boxes[index] else {
+                        continue;
+                    };
+                    let clipped = clip.is_some_and(|clip| !contains(&clip, &bbox));
+                    let covered = self.boxes[index + 1..]
+                        .iter()
+                        .flatten()
+                        .any(|later| overlaps(later, &bbox));
+                    if clipped || covered {
+                        continue;
+                    }
+                    if let Some(runs) = fonts.layout(text, self.page, next_ref) {
+                        self.runs.extend(runs);
+                        text.flattened = None;
+                    }
+                }
+                Node::Path(_) | Node::Image(_) => self.drawn += 1,
+            }
+        }
+    }
+}
+
+/// Add the bounding box of every path, image and text of `group` to `boxes`, in
+/// paint order.
+fn drawn_boxes(group: &usvg::Group, boxes: &mut Vec<Option<NonZeroRect>>) {
+    for node in &group.children {
+        match node {
+            Node::Group(group) => drawn_boxes(group, boxes),
+            _ => boxes.push(node.abs_stroke_bounding_box()),
+        }
+    }
+}
+
+/// The rectangle a clip path leaves visible in user space, `None` unless the clip path
+/// is a single rectangle, not rotated or skewed.
+fn clip_rect(clip_path: &usvg::ClipPath, transform: Transform) -> Option<NonZeroRect> {
+    if clip_path.units != Units::UserSpaceOnUse || clip_path.clip_path.is_some() {
+        return None;
+    }
+    let transform = transform.pre_concat(clip_path.transform);
+    if transform.kx != 0.0 || transform.ky != 0.0 {
+        return None;
+    }
+    let [Node::Path(path)] = clip_path.root.children.as_slice() else {
+        return None;
+    };
+    let bounds = path.data.bounds();
+    let is_rect = path.data.points().iter().all(|p| {
+        (p.x == bounds.left() || p.x == bounds.right())
+            && (p.y == bounds.top() || p.y == bounds.bottom())
+    });
+    if !is_rect {
+        return None;
+    }
+    bounds.to_non_zero_rect()?.transform(transform)
+}
+
+fn contains(outer: &NonZeroRect, inner: &NonZeroRect) -> bool {
+    outer.left() <= inner.left()
+        && outer.top() <= inner.top()
+        && outer.right() >= inner.right()
+        && outer.bottom() >= inner.bottom()
+}
+
+fn intersect(a: &NonZeroRect, b: &NonZeroRect) -> Option<NonZeroRect> {
+    NonZeroRect::from_ltrb(
+        a.left().max(b.left()),
+        a.top().max(b.top()),
+        a.right().min(b.right()),
+        a.bottom().min(b.bottom()),
+    )
+}
+
+fn overlaps(a: &NonZeroRect, b: &NonZeroRect) -> bool {
+    a.left() < b.right() && b.left() < a.right() && a.top() < b.bottom() && b.top() < a.bottom()
+}
+
+/// Whether `c` is written right to left, which needs a bidirectional layout.
+fn is_rtl(c: char) -> bool {
+    matches!(
+        unicode_bidi::bidi_class(c),
+        BidiClass::R | BidiClass::AL | BidiClass::RLE | BidiClass::RLO | BidiClass::RLI
+    )
+}
+
+/// The characters usvg adds the word spacing after.
+fn is_word_separator(c: char) -> bool {
+    matches!(
+        c,
+        ' ' | '\u{00A0}' | '\u{1361}' | '\u{10100}' | '\u{10101}' | '\u{1039F}' | '\u{1091F}'
+    )
+}
+
+/// The flags of a font descriptor. A font is symbolic unless it has the Latin letters.
+fn flags(face: &ttf_parser::Face) -> FontFlags {
+    let latin = ('a'..='z')
+        .chain('A'..='Z')
+        .all(|c| face.glyph_index(c).is_some());
+    let mut flags = if latin {
+        FontFlags::NON_SYMBOLIC
+    } else {
+        FontFlags::SYMBOLIC
+    };
+    flags.set(FontFlags::ITALIC, face.is_italic());
+    flags.set(FontFlags::FIXED_PITCH, face.is_monospaced());
+    flags
+}
+
+/// Draw `runs` onto a page.
+pub(crate) fn draw(runs: &[TextRun], content: &mut Content) {
+    for run in runs {
+        let m = run.matrix;
+        content
+            .begin_text()
+            .set_fill_rgb(run.color[0], run.color[1], run.color[2])
+            .set_font(Name(font_name(run.font).as_bytes()), run.size)
+            .set_text_matrix([m.sx, m.ky, m.kx, m.sy, m.tx, m.ty]);
+        let mut show = content.show_positioned();
+        let mut items = show.items();
+        let mut glyphs = vec![];
+        for (glyph, adjust) in &run.glyphs {
+            if adjust.abs() > 0.01 {
+                items.show(Str(&glyphs));
+                items.adjust(*adjust);
+                glyphs.clear();
+            }
+            glyphs.extend(glyph.to_be_bytes());
+        }
+        items.show(Str(&glyphs));
+        items.finish();
+        show.finish();
+        content.end_text();
+    }
+}
+
+/// Split the glyphs of a chunk starting at `x`, `y` into runs.
+fn runs_of(placed: &[Placed], transform: Transform, x: f32, y: f32) -> Vec<TextRun> {
+    let mut runs: Vec<TextRun> = vec![];
+    // The pen position the current run reached, in its own text space.
+    let mut end = 0.0;
+    let mut start = (0.0, 0.0);
+    for glyph in placed {
+        let Some(color) = glyph.color else {
+            continue;
+        };
+        let continues = runs.last().is_some_and(|run| {
+            run.font == glyph.font
+                && run.size == glyph.size
+                && run.color == color
+                && start.1 == glyph.y
+        });
+        if continues {
+            let run = runs.last_mut().expect("run continues");
+            let gap = glyph.x - start.0 - end;
+            run.glyphs.push((glyph.glyph, -gap * 1000.0 / glyph.size));
+            end += gap;
+        } else {
+            start = (glyph.x, glyph.y);
+            end = 0.0;
+            runs.push(TextRun {
+                font: glyph.font,
+                size: glyph.size,
+                color,
+                matrix: transform
+                    .pre_translate(x + glyph.x, y + glyph.y)
+                    .pre_scale(1.0, -1.0),
+                glyphs: vec![(glyph.glyph, 0.0)],
+            });
+        }
+        end += glyph.advance * glyph.size;
+    }
+    runs
+}
+
+/// Whether a span is drawn with nothing but its glyphs.
+fn is_plain(span: &usvg::TextSpan) -> bool {
+    span.stroke.is_none()
+        && !span.small_caps
+        && span.decoration.underline.is_none()
+        && span.decoration.overline.is_none()
+        && span.decoration.line_through.is_none()
+        && span
+            .baseline_shift
+            .iter()
+            .all(|shift| matches!(shift, BaselineShift::Baseline))
+        && matches!(
+            span.dominant_baseline,
+            DominantBaseline::Auto | DominantBaseline::Alphabetic
+        )
+        && matches!(
+            span.alignment_baseline,
+            AlignmentBaseline::Auto | AlignmentBaseline::Baseline | AlignmentBaseline::Alphabetic
+        )
+}
+
+fn stretch(stretch: FontStretch) -> fontdb::Stretch {
+    match stretch {
+        FontStretch::UltraCondensed => fontdb::Stretch::UltraCondensed,
+        FontStretch::ExtraCondensed => fontdb::Stretch::ExtraCondensed,
+        FontStretch::Condensed => fontdb::Stretch::Condensed,
+        FontStretch::SemiCondensed => fontdb::Stretch::SemiCondensed,
+        FontStretch::Normal => fontdb::Stretch::Normal,
+        FontStretch::SemiExpanded => fontdb::Stretch::SemiExpanded,
+        FontStretch::Expanded => fontdb::Stretch::Expanded,
+        FontStretch::ExtraExpanded => fontdb::Stretch::ExtraExpanded,
+        FontStretch::UltraExpanded => fontdb::Stretch::UltraExpanded,
+    }
+}
+
+fn font_name(index: usize) -> String {
+    format!("F{}", index + 1)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::woff2::sfnt;
+    use miniz_oxide::inflate::decompress_to_vec_zlib;
+    use usvg::{PostProcessingSteps, TreeParsing, TreePostProc};
+
+    fn be16(values: &[i32]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|v| (*v as u16).to_be_bytes())
+            .collect()
+    }
+
+    /// A TrueType font named Test with a space, the Latin letters drawn as squares, and
+    /// a kerning pair for AV.
+    pub(crate) fn font() -> Vec<u8> {
+        let num_glyphs = 1 + 1 + 26 + 26;
+        let square = [
+            be16(&[1, 50, 0, 450, 700, 3, 0]),
+            vec![0x01; 4],
+            be16(&[50, 400, 0, -400, 0, 0, 700, 0]),
+        ]
+        .concat();
+        let mut glyf = vec![];
+        let mut loca = vec![];
+        for glyph in 0..num_glyphs {
+            loca.extend((glyf.len() as u32).to_be_bytes());
+            if glyph != 1 {
+                glyf.extend(&square);
+                glyf.resize(glyf.len().next_multiple_of(4), 0);
+            }
+        }
+        loca.extend((glyf.len() as u32).to_be_bytes());
+        let mut head = be16(&[1, 0, 1, 0, 0, 0, 0x5f0f, 0x3cf5, 0, 1000]);
+        head.extend([0; 16]);
+        head.extend(be16(&[0, -200, 500, 800, 0, 8, 2, 1, 0]));
+        let mut hhea = be16(&[1, 0, 800, -200, 0, 500]);
+        hhea.resize(34, 0);
+        hhea.extend(be16(&[num_glyphs]));
+        let maxp = be16(&[0, 0x5000, num_glyphs]);
+        let hmtx: Vec<i32> = (0..num_glyphs)
+            .flat_map(|glyph| [if glyph == 1 { 250 } else { 500 }, 50])
+            .collect();
+        // Format 4 segments for the space, A to Z and a to z.
+        let cmap = [
+            be16(&[0, 1, 3, 1, 0, 12]),
+            be16(&[4, 48, 0, 8, 8, 2, 0]),
+            be16(&[0x20, 0x5a, 0x7a, 0xffff, 0]),
+            be16(&[0x20, 0x41, 0x61, 0xffff]),
+            be16(&[1 - 0x20, 2 - 0x41, 28 - 0x61, 1]),
+            be16(&[0; 4]),
+        ]
+        .concat();
+        let kern = be16(&[0, 1, 0, 20, 1, 1, 6, 0, 0, 2, 23, -80]);
+        let names = ["Test", "Test-Regular"].map(|name| {
+            name.encode_utf16()
+                .flat_map(|c| c.to_be_bytes())
+                .collect::<Vec<u8>>()
+        });
+        let mut name = be16(&[0, 2, 30]);
+        name.extend(be16(&[3, 1, 0x409, 1, names[0].len() as i32, 0]));
+        name.extend(be16(&[
+            3,
+            1,
+            0x409,
+            6,
+            names[1].len() as i32,
+            names[0].len() as i32,
+        ]));
+        name.extend(names.concat());
+        sfnt(
+            0x0001_0000,
+            vec![
+                (*b"cmap", cmap),
+                (*b"glyf", glyf),
+                (*b"head", head),
+                (*b"hhea", hhea),
+                (*b"hmtx", be16(&hmtx)),
+                (*b"kern", kern),
+                (*b"loca", loca),
+                (*b"maxp", maxp),
+                (*b"name", name),
+            ],
+        )
+    }
+
+    fn fonts() -> Fonts {
+        let mut db = fontdb::Database::new();
+        db.load_font_data(font());
+        Fonts::new(Arc::new(db))
+    }
+
+    fn tree(svg: &str, fonts: &Fonts) -> usvg::Tree {
+        let mut tree = usvg::Tree::from_str(svg, &usvg::Options::default()).unwrap();
+        tree.postprocess(PostProcessingSteps::default(), &fonts.db);
+        tree
+    }
+
+    /// Take the text out of `svg` and write it with its fonts, returning the page
+    /// content and the PDF.
+    fn render(svg: &str) -> (String, Vec<u8>) {
+        let mut fonts = fonts();
+        let mut tree = tree(svg, &fonts);
+        let text = fonts.take_text(&mut tree.root, Transform::default(), &mut Ref::new(1));
+        let mut content = Content::new();
+        draw(&text, &mut content);
+        let mut pdf = Pdf::new();
+        fonts.write(&mut pdf).unwrap();
+        (
+            String::from_utf8_lossy(&content.finish()).into_owned(),
+            pdf.finish(),
+        )
+    }
+
+    /// The dictionary and the decompressed stream of an object of `pdf`.
+    fn object(pdf: &[u8], id: i32) -> (String, Vec<u8>) {
+        let find =
+            |data: &[u8], what: &[u8]| data.windows(what.len()).position(|window| window == what);
+        let start = find(pdf, format!("\n{} 0 obj\n", id).as_bytes()).unwrap();
+        let object = &pdf[start..];
+        let object = &object[..find(object, b"endobj").unwrap()];
+        let Some(split) = find(object, b"stream\n") else {
+            return (String::from_utf8_lossy(object).into_owned(), vec![]);
+        };
+        let dict = String::from_utf8_lossy(&object[..split]).into_owned();
+        let stream = &object[split + 7..];
+        let stream = &stream[..find(stream, b"\nendstream").unwrap()];
+        (dict, decompress_to_vec_zlib(stream).unwrap())
+    }
+
+    fn has_outline(font: &[u8], glyph: u16) -> bool {
+        let face = ttf_parser::Face::parse(font, 0).unwrap();
+        face.glyph_bounding_box(ttf_parser::GlyphId(glyph))
+            .is_some()
+    }
+
+    #[test]
+    fn embeds_shaped_text_with_subset_fonts() {
+        let (content, pdf) = render(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+                <text x="10" y="50" font-family="Test" font-size="20">AVa b</text>
+            </svg>"#,
+        );
+        assert!(content.contains("/F1 20 Tf"), "{}", content);
+        // The kerning of AV, in thousandths of the font size.
+        assert!(content.contains(r"[(\000\002) 80.0"), "{}", content);
+        assert!(content.contains("TJ"), "{}", content);
+
+        let (type0, _) = object(&pdf, 1);
+        assert!(type0.contains("/Subtype /Type0"), "{}", type0);
+        assert!(type0.contains("+Test-Regular"), "{}", type0);
+        assert!(type0.contains("/Encoding /Identity-H"), "{}", type0);
+        let (cid, _) = object(&pdf, 2);
+        assert!(cid.contains("/Subtype /CIDFontType2"), "{}", cid);
+        assert!(
+            cid.contains("/W [1 [250] 2 [500] 23 [500] 28 [500] 29 [500]]"),
+            "{}",
+            cid
+        );
+        let (descriptor, _) = object(&pdf, 3);
+        assert!(descriptor.contains("/Flags 32"), "{}", descriptor);
+        assert!(descriptor.contains("/FontFile2 4 0 R"), "{}", descriptor);
+
+        let (file, subset) = object(&pdf, 4);
+        assert!(
+            file.contains(&format!("/Length1 {}", subset.len())),
+            "{}",
+            file
+        );
+        assert!(subset.len() < font().len());
+        // A, b and z.
+        assert!(has_outline(&subset, 2));
+        assert!(has_outline(&subset, 29));
+        assert!(!has_outline(&subset, 53));
+
+        let (_, cmap) = object(&pdf, 5);
+        let cmap = String::from_utf8(cmap).unwrap();
+        for pair in ["<0002> <0041>", "<0017> <0056>", "<0001> <0020>"] {
+            assert!(cmap.contains(pair), "{}", cmap);
+        }
+    }
+
+    /// The text of every `<text>` of `svg` that was taken out of the tree.
+    fn taken(svg: &str) -> Vec<bool> {
+        let mut fonts = fonts();
+        let mut tree = tree(svg, &fonts);
+        fonts.take_text(&mut tree.root, Transform::default(), &mut Ref::new(1));
+        let mut taken = vec![];
+        let mut groups = vec![&tree.root];
+        while let Some(group) = groups.pop() {
+            for node in &group.children {
+                match node {
+                    Node::Group(group) => groups.push(group),
+                    Node::Text(text) => taken.push(text.flattened.is_none()),
+                    _ => {}
+                }
+            }
+        }
+        taken
+    }
+
+    #[test]
+    fn keeps_outlines_where_glyphs_cannot_show_the_text() {
+        let svg = |body: &str| {
+            format!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100">
+                    <clipPath id="rect"><rect x="0" y="0" width="100" height="100"/></clipPath>
+                    <clipPath id="circle"><circle cx="50" cy="50" r="50"/></clipPath>
+                    {}
+                </svg>"#,
+                body
+            )
+        };
+        let text = r#"<text x="10" y="50" font-family="Test" font-size="20">Ab</text>"#;
+        assert_eq!(taken(&svg(text)), [true]);
+        // Drawn over.
+        let covered = format!(r#"{}<rect x="0" y="0" width="30" height="60"/>"#, text);
+        assert_eq!(taken(&svg(&covered)), [false]);
+        let beside = format!(r#"{}<rect x="150" y="0" width="30" height="60"/>"#, text);
+        assert_eq!(taken(&svg(&beside)), [true]);
+        // Clipped by a rectangle around it, by one cutting it and by a circle.
+        let clipped = |clip: &str, x: i32| {
+            svg(&format!(
+                r#"<g clip-path="url(#{})"><text x="{}" y="50" font-family="Test" font-size="20">Ab</text></g>"#,
+                clip, x
+            ))
+        };
+        assert_eq!(taken(&clipped("rect", 10)), [true]);
+        assert_eq!(taken(&clipped("rect", 90)), [false]);
+        assert_eq!(taken(&clipped("circle", 10)), [false]);
+        // Right to left, and a character the font does not have.
+        let text = |content: &str| {
+            svg(&format!(
+                r#"<text x="10" y="50" font-family="Test" font-size="20">{}</text>"#,
+                content
+            ))
+        };
+        assert_eq!(taken(&text("A\u{202e}b")), [false]);
+        assert_eq!(taken(&text("A\u{e9}")), [false]);
+    }
+}
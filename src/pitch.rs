@@ -0,0 +1,190 @@
+//! Turning a position on a staff into a pitch, for note entry.
+
+use crate::VerovioToolkit;
+use crate::geometry::{self, Transform, has_class};
+use anyhow::anyhow;
+
+const PITCH_NAMES: [&str; 7] = ["c", "d", "e", "f", "g", "a", "b"];
+
+/// A clef drawn on a staff, in page units.
+struct Clef {
+    x: f64,
+    y: f64,
+    code: String,
+}
+
+impl VerovioToolkit {
+    /// Get the pitch name and octave of a position on a staff, for an `insert` edit action.
+    ///
+    /// The position is in SVG pixels, or in the units of the viewBox with `svg_view_box`,
+    /// as for [`VerovioToolkit::element_at`]. The pitch follows the staff lines and the
+    /// clef in effect at `x`, that is the last clef drawn before it on the staff within
+    /// the system.
+    pub fn pitch_at(
+        &self,
+        page_no: i32,
+        staff_id: &str,
+        x: f64,
+        y: f64,
+    ) -> anyhow::Result<(&'static str, i32)> {
+        let svg = self.render_to_svg(page_no, false)?;
+        let (x, y) = geometry::parse(&svg)?.to_page_units(x, y);
+        let doc = roxmltree::Document::parse(&svg)?;
+        let staff = doc
+            .descendants()
+            .find(|n| n.attribute("id").or_else(|| n.attribute("data-id")) == Some(staff_id))
+            .filter(|n| has_class(*n, "staff"))
+            .ok_or_else(|| anyhow!("staff {} is not on page {}", staff_id, page_no))?;
+        let lines = staff_lines(staff);
+        let (Some(&top), Some(&bottom)) = (lines.first(), lines.last()) else {
+            return Err(anyhow!("staff {} has no lines", staff_id));
+        };
+        if lines.len() < 2 {
+            return Err(anyhow!("staff {} has a single line", staff_id));
+        }
+        let step = (bottom - top) / (lines.len() - 1) as f64 / 2.0;
+
+        // The clef of a system is drawn in its first measure, clef changes in any measure,
+        // so the clefs of the staff in every measure of the system count.
+        let system = staff
+            .ancestors()
+            .find(|n| has_class(*n, "system"))
+            .unwrap_or(staff);
+        let mut clefs: Vec<Clef> = system
+            .descendants()
+            .filter(|n| has_class(*n, "staff"))
+            .filter(|s| {
+                staff_lines(*s)
+                    .first()
+                    .is_some_and(|t| (t - top).abs() < step)
+            })
+            .flat_map(|s| s.descendants().filter(|n| has_class(*n, "clef")))
+            .filter_map(clef)
+            .collect();
+        clefs.sort_by(|a, b| a.x.total_cmp(&b.x));
+        let clef = clefs
+            .iter()
+            .rev()
+            .find(|c| c.x <= x)
+            .or(clefs.first())
+            .ok_or_else(|| anyhow!("staff {} has no clef", staff_id))?;
+
+        let reference = clef_pitch(&clef.code)
+            .ok_or_else(|| anyhow!("clef glyph {} has no pitch", clef.code))?;
+        Ok(pitch(reference, steps_above_clef(bottom, step, clef.y, y)))
+    }
+}
+
+/// The pitch, as a step in `PITCH_NAMES` and an octave, of the line a SMuFL clef glyph
+/// is drawn on, that is the line of its origin.
+fn clef_pitch(code: &str) -> Option<(i32, i32)> {
+    Some(match code {
+        // gClef, its arrowed, old-style, parenthesised and change variants
+        "E050" | "E05A" | "E05B" | "E07A" => (4, 4),
+        "E051" => (4, 2),
+        "E052" | "E055" | "E056" | "E057" => (4, 3),
+        "E053" => (4, 5),
+        "E054" => (4, 6),
+        // cClef, its arrowed, square and change variants, and the chant C clef
+        "E05C" | "E05E" | "E05F" | "E060" | "E07B" | "E906" => (0, 4),
+        "E05D" => (0, 3),
+        // fClef, its arrowed and change variants, and the chant F clef
+        "E062" | "E067" | "E068" | "E07C" | "E902" => (3, 3),
+        "E063" => (3, 1),
+        "E064" => (3, 2),
+        "E065" => (3, 4),
+        "E066" => (3, 5),
+        _ => return None,
+    })
+}
+
+/// The number of staff steps from the line of a clef drawn at `clef_y` to `y`, upwards.
+///
+/// The clef is taken to be on the nearest line, counted from the `bottom` one, and `y`
+/// on the nearest line or space.
+fn steps_above_clef(bottom: f64, step: f64, clef_y: f64, y: f64) -> i32 {
+    let clef_line = ((bottom - clef_y) / (2.0 * step)).round();
+    (((bottom - y) / step).round() - 2.0 * clef_line) as i32
+}
+
+/// The pitch name and octave `steps` staff steps above the `reference` pitch of a clef.
+fn pitch((pname, oct): (i32, i32), steps: i32) -> (&'static str, i32) {
+    let pitch = oct * 7 + pname + steps;
+    (
+        PITCH_NAMES[pitch.rem_euclid(7) as usize],
+        pitch.div_euclid(7),
+    )
+}
+
+/// The y of the lines of a staff in page units, top first.
+fn staff_lines(staff: roxmltree::Node) -> Vec<f64> {
+    let transform = Transform::of(staff);
+    let mut lines: Vec<f64> = staff
+        .children()
+        .filter(|n| n.has_tag_name("path"))
+        .filter_map(|n| {
+            let mut numbers = n
+                .attribute("d")?
+                .trim_start_matches('M')
+                .split([' ', ',', 'L']);
+            let x = numbers.find(|v| !v.is_empty())?.parse().ok()?;
+            let y = numbers.find(|v| !v.is_empty())?.parse().ok()?;
+            Some(transform.apply_point(x, y).1)
+        })
+        .collect();
+    lines.sort_by(f64::total_cmp);
+    lines
+}
+
+/// The glyph and origin of a clef element.
+fn clef(node: roxmltree::Node) -> Option<Clef> {
+    let glyph = node.descendants().find(|n| n.has_tag_name("use"))?;
+    let href = glyph
+        .attribute("href")
+        .or_else(|| glyph.attribute(("http://www.w3.org/1999/xlink", "href")))?;
+    let code = href
+        .trim_start_matches('#')
+        .split('-')
+        .next()?
+        .to_uppercase();
+    let number = |name| glyph.attribute(name).and_then(|v| v.parse::<f64>().ok());
+    let transform = Transform::of(glyph);
+    let (x, y) = transform.apply_point(number("x").unwrap_or(0.0), number("y").unwrap_or(0.0));
+    Some(Clef { x, y, code })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clef_pitches() {
+        let at = |code| clef_pitch(code).map(|reference| pitch(reference, 0));
+        assert_eq!(at("E050"), Some(("g", 4)));
+        assert_eq!(at("E052"), Some(("g", 3)));
+        assert_eq!(at("E05C"), Some(("c", 4)));
+        assert_eq!(at("E05D"), Some(("c", 3)));
+        assert_eq!(at("E062"), Some(("f", 3)));
+        assert_eq!(at("E063"), Some(("f", 1)));
+        // Percussion clefs have no pitch.
+        assert_eq!(at("E069"), None);
+    }
+
+    #[test]
+    fn steps() {
+        // Lines 10 apart from 0 to 40, a treble clef on the second line from the bottom.
+        assert_eq!(steps_above_clef(40.0, 5.0, 30.0, 30.0), 0);
+        assert_eq!(steps_above_clef(40.0, 5.0, 31.5, 24.0), 1);
+        assert_eq!(steps_above_clef(40.0, 5.0, 30.0, 50.0), -4);
+        assert_eq!(steps_above_clef(40.0, 5.0, 30.0, -10.0), 8);
+    }
+
+    #[test]
+    fn pitches_across_octaves() {
+        assert_eq!(pitch((4, 4), 1), ("a", 4));
+        assert_eq!(pitch((4, 4), 3), ("c", 5));
+        assert_eq!(pitch((4, 4), -4), ("c", 4));
+        assert_eq!(pitch((4, 4), -5), ("b", 3));
+        assert_eq!(pitch((0, 0), -1), ("b", -1));
+    }
+}
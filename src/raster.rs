@@ -0,0 +1,88 @@
+//! Rendering pages to bitmaps with resvg.
+
+use crate::{Coloring, VerovioToolkit, svg_tree};
+use anyhow::anyhow;
+use resvg::tiny_skia::{Color, Pixmap, Transform};
+use std::path::Path;
+
+/// How pages are rendered to bitmaps.
+#[derive(Debug, Clone)]
+pub struct RasterOptions {
+    /// Resolution of the page, whose size is set in tenths of a millimetre by the
+    /// `page_width` and `page_height` options, whatever the `scale`.
+    pub dpi: f32,
+    /// Colour behind the page.
+    pub background: (u8, u8, u8),
+    /// Leave the background transparent instead.
+    pub transparent: bool,
+    /// Colour the staves, layers or parts.
+    pub coloring: Option<Coloring>,
+}
+
+impl Default for RasterOptions {
+    fn default() -> Self {
+        RasterOptions {
+            dpi: 96.0,
+            background: (255, 255, 255),
+            transparent: false,
+            coloring: None,
+        }
+    }
+}
+
+impl VerovioToolkit {
+    /// Render a page to PNG at the given resolution, on a white background.
+    pub fn render_to_png(&self, page_no: i32, dpi: f32) -> anyhow::Result<Vec<u8>> {
+        let options = RasterOptions {
+            dpi,
+            ..Default::default()
+        };
+        self.render_to_png_with(page_no, &options)
+    }
+
+    /// Render a page to PNG.
+    pub fn render_to_png_with(
+        &self,
+        page_no: i32,
+        options: &RasterOptions,
+    ) -> anyhow::Result<Vec<u8>> {
+        Ok(self.render_to_pixmap(page_no, options)?.encode_png()?)
+    }
+
+    /// Render a page to a bitmap.
+    pub fn render_to_pixmap(
+        &self,
+        page_no: i32,
+        options: &RasterOptions,
+    ) -> anyhow::Result<Pixmap> {
+        let svg = self.render_to_svg_for_output(page_no, options.coloring.as_ref())?;
+        let resource_path = self.get_resource_path()?;
+        rasterize(&svg, Path::new(&resource_path), self.get_scale()?, options)
+    }
+}
+
+/// Render an SVG produced by verovio at the given `scale` option, with the fonts of the
+/// resource path and those installed on the system.
+fn rasterize(
+    svg: &str,
+    resource_path: &Path,
+    verovio_scale: i32,
+    options: &RasterOptions,
+) -> anyhow::Result<Pixmap> {
+    let tree = svg_tree::parse(svg, resource_path)?;
+    let scale = options.dpi * svg_tree::units_per_svg_px(verovio_scale) / svg_tree::UNITS_PER_INCH;
+    let width = (tree.size.width() * scale).ceil() as u32;
+    let height = (tree.size.height() * scale).ceil() as u32;
+    let mut pixmap = Pixmap::new(width, height)
+        .ok_or_else(|| anyhow!("cannot render a page of {}x{} pixels", width, height))?;
+    if !options.transparent {
+        let (r, g, b) = options.background;
+        pixmap.fill(Color::from_rgba8(r, g, b, 255));
+    }
+    resvg::render(
+        &tree,
+        Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+    Ok(pixmap)
+}
@@ -0,0 +1,180 @@
+//! Verovio resources bundled with the `embedded-resources` feature.
+
+use crate::REQUIRED_RESOURCES;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+include!(concat!(env!("OUT_DIR"), "/resources.rs"));
+
+static RESOURCE_PATH: OnceLock<Result<PathBuf, String>> = OnceLock::new();
+
+/// Write the embedded resources to disk once and return their directory.
+///
+/// The directory is created in the user's cache directory and named after the crate
+/// version and a hash of the resources, so processes of the same build share it.
+/// It is checked against the embedded resources before use and written again when
+/// files are missing or were changed. Without a cache directory, a fresh temporary
+/// directory is used by each process.
+pub fn embedded_resource_path() -> anyhow::Result<&'static Path> {
+    RESOURCE_PATH
+        .get_or_init(|| materialize().map_err(|err| err.to_string()))
+        .as_deref()
+        .map_err(|err| anyhow::anyhow!("unable to write embedded verovio resources: {}", err))
+}
+
+fn materialize() -> io::Result<PathBuf> {
+    let name = format!(
+        "verovior-{}-{:016x}",
+        env!("CARGO_PKG_VERSION"),
+        RESOURCES_HASH
+    );
+    let Some(cache) = cache_dir() else {
+        return materialize_temp(&name);
+    };
+    if create_private_dir_all(&cache).is_err() || !is_private(&cache) {
+        return materialize_temp(&name);
+    }
+    let dir = cache.join(&name);
+    if is_valid(&dir) {
+        return Ok(dir);
+    }
+
+    // Write to a directory of our own first and rename it, so that no process
+    // ever sees a partially written tree.
+    let staging = cache.join(format!("{}.{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&staging);
+    write_resources(&staging)?;
+    if dir.exists() {
+        let _ = fs::remove_dir_all(&dir);
+    }
+    match fs::rename(&staging, &dir) {
+        Ok(()) => Ok(dir),
+        // Another process got there first.
+        Err(_) if is_valid(&dir) => {
+            let _ = fs::remove_dir_all(&staging);
+            Ok(dir)
+        }
+        Err(err) => {
+            let _ = fs::remove_dir_all(&staging);
+            Err(err)
+        }
+    }
+}
+
+/// Write the resources to a new directory in the temporary directory.
+fn materialize_temp(name: &str) -> io::Result<PathBuf> {
+    let temp = std::env::temp_dir();
+    for attempt in 0..16 {
+        let dir = temp.join(format!(
+            "{}.{}.{}.{}",
+            name,
+            std::process::id(),
+            nanos(),
+            attempt
+        ));
+        match create_private_dir(&dir) {
+            Ok(()) => {
+                write_resources(&dir)?;
+                return Ok(dir);
+            }
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        "unable to create a temporary directory",
+    ))
+}
+
+fn write_resources(dir: &Path) -> io::Result<()> {
+    create_private_dir_all(dir)?;
+    for (file, content) in RESOURCES {
+        let path = dir.join(file);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, content)?;
+    }
+    Ok(())
+}
+
+/// Whether `dir` is ours and holds every embedded file at its size, the files
+/// verovio needs to start matching the embedded ones byte for byte.
+fn is_valid(dir: &Path) -> bool {
+    if !is_private(dir) {
+        return false;
+    }
+    RESOURCES.iter().all(|(file, content)| {
+        let path = dir.join(file);
+        if REQUIRED_RESOURCES.contains(file) {
+            fs::read(&path).is_ok_and(|data| data == *content)
+        } else {
+            fs::metadata(&path).is_ok_and(|m| m.is_file() && m.len() == content.len() as u64)
+        }
+    })
+}
+
+fn nanos() -> u32 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0)
+}
+
+/// The user's cache directory, following the XDG convention on Unix.
+fn cache_dir() -> Option<PathBuf> {
+    let non_empty = |var: &str| {
+        std::env::var_os(var)
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+    };
+    if cfg!(windows) {
+        non_empty("LOCALAPPDATA")
+    } else if cfg!(target_os = "macos") {
+        non_empty("HOME").map(|home| home.join("Library/Caches"))
+    } else {
+        non_empty("XDG_CACHE_HOME").or_else(|| non_empty("HOME").map(|home| home.join(".cache")))
+    }
+    .filter(|dir| dir.is_absolute())
+}
+
+#[cfg(unix)]
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+    fs::DirBuilder::new().mode(0o700).create(dir)
+}
+
+#[cfg(not(unix))]
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    fs::create_dir(dir)
+}
+
+#[cfg(unix)]
+fn create_private_dir_all(dir: &Path) -> io::Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+    fs::DirBuilder::new()
+        .mode(0o700)
+        .recursive(true)
+        .create(dir)
+}
+
+#[cfg(not(unix))]
+fn create_private_dir_all(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)
+}
+
+/// Whether `dir` is a directory owned by the current user that others cannot write to.
+#[cfg(unix)]
+fn is_private(dir: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    fs::symlink_metadata(dir)
+        .is_ok_and(|m| m.is_dir() && m.uid() == unsafe { libc::geteuid() } && m.mode() & 0o022 == 0)
+}
+
+#[cfg(not(unix))]
+fn is_private(dir: &Path) -> bool {
+    fs::symlink_metadata(dir).is_ok_and(|m| m.is_dir())
+}
@@ -0,0 +1,90 @@
+//! Styling elements of the rendered SVG, such as the notes being played.
+
+use crate::VerovioToolkit;
+use crate::svg::{attributes, classes, escape, rewrite_with, set, write_tag};
+use std::collections::HashMap;
+
+/// How an element is drawn differently from the way verovio draws it.
+#[derive(Debug, Clone, Default)]
+pub struct Style {
+    /// A CSS class added to the element.
+    pub class: Option<String>,
+    /// A colour, for both `fill` and `color` since verovio draws lines with `currentColor`.
+    pub fill: Option<String>,
+    pub opacity: Option<f32>,
+}
+
+/// Styles applied by [`VerovioToolkit::render_to_svg_with`].
+///
+/// When an element matches both maps, the style of its id applies last.
+#[derive(Debug, Clone, Default)]
+pub struct Styling {
+    /// Styles by element id.
+    pub ids: HashMap<String, Style>,
+    /// Styles by element class, that is by element type such as `note` or `beam`.
+    pub classes: HashMap<String, Style>,
+}
+
+impl VerovioToolkit {
+    /// Render a page to SVG with the attributes of `styling` set on the matching elements.
+    ///
+    /// The SVG keeps the structure verovio gives it, the attributes being added to the
+    /// groups of the elements, so styles can change without the document being laid out again.
+    pub fn render_to_svg_with(&self, page_no: i32, styling: &Styling) -> anyhow::Result<String> {
+        let svg = self.render_to_svg(page_no, false)?;
+        Ok(styling.apply(&svg))
+    }
+}
+
+impl Styling {
+    /// Set the styles on the matching elements of an SVG rendered by verovio.
+    pub fn apply(&self, svg: &str) -> String {
+        rewrite_with(
+            svg,
+            |tag, out| self.style_tag(tag, out),
+            |css, out| *out += css,
+        )
+    }
+
+    fn style_tag(&self, tag: &str, out: &mut String) {
+        let mut attributes = attributes(tag);
+        let get = |attributes: &[(String, String)], name: &str| {
+            attributes
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, v)| v.clone())
+        };
+        let id = get(&attributes, "id").or_else(|| get(&attributes, "data-id"));
+        let classes = classes(&attributes).unwrap_or_default().to_string();
+        let mut styles: Vec<&Style> = classes
+            .split_whitespace()
+            .filter_map(|class| self.classes.get(class))
+            .collect();
+        styles.extend(id.and_then(|id| self.ids.get(&id)));
+        if styles.is_empty() {
+            *out += tag;
+            return;
+        }
+
+        for style in styles {
+            if let Some(class) = &style.class {
+                match attributes.iter_mut().find(|(n, _)| n == "class") {
+                    Some((_, value)) => {
+                        value.push(' ');
+                        value.push_str(&escape(class));
+                    }
+                    None => attributes.push(("class".into(), escape(class))),
+                }
+            }
+            if let Some(fill) = &style.fill {
+                set(&mut attributes, "fill", escape(fill));
+                set(&mut attributes, "color", escape(fill));
+            }
+            if let Some(opacity) = style.opacity {
+                set(&mut attributes, "opacity", opacity.to_string());
+            }
+        }
+
+        write_tag(tag, &attributes, out);
+    }
+}
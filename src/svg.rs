@@ -0,0 +1,385 @@
+//! Post-processing of the SVG verovio renders, so that several pages can share an HTML document.
+
+use crate::{VerovioToolkit, text_fonts};
+use anyhow::anyhow;
+use std::fs;
+use std::path::Path;
+
+/// How [`VerovioToolkit::postprocess_svg`] rewrites an SVG.
+#[derive(Debug, Clone, Default)]
+pub struct InlineOptions {
+    /// Prepended to every `id` and to the references to them,
+    /// in `href`, `xlink:href` and `url(#...)`.
+    pub id_prefix: Option<String>,
+    /// Replace the `@import` of the SMuFL text font with its `@font-face` rules,
+    /// read from the resource path, and add a stylesheet embedding the faces of the text
+    /// fonts used, found in the resource path or installed on the system.
+    pub embed_fonts: bool,
+}
+
+impl VerovioToolkit {
+    /// Render a page to SVG and post-process it with [`VerovioToolkit::postprocess_svg`].
+    pub fn render_to_svg_inline(
+        &self,
+        page_no: i32,
+        options: &InlineOptions,
+    ) -> anyhow::Result<String> {
+        let svg = self.render_to_svg(page_no, false)?;
+        self.postprocess_svg(&svg, options)
+    }
+
+    /// Rewrite an SVG rendered by verovio so that it can be inlined in an HTML document
+    /// next to others. Works with and without the `svg_html5` and `svg_remove_xlink` options.
+    pub fn postprocess_svg(&self, svg: &str, options: &InlineOptions) -> anyhow::Result<String> {
+        if let Some(prefix) = &options.id_prefix {
+            let valid = prefix
+                .chars()
+                .next()
+                .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+                && prefix
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+            if !valid {
+                return Err(anyhow!("{:?} cannot start an XML id", prefix));
+            }
+        }
+        let resource_path = if options.embed_fonts {
+            Some(self.get_resource_path()?)
+        } else {
+            None
+        };
+        let resource_path = resource_path.as_deref().map(Path::new);
+        let text_fonts = resource_path.map(|path| text_fonts::font_faces(svg, path));
+        Ok(rewrite(
+            svg,
+            options.id_prefix.as_deref(),
+            resource_path,
+            text_fonts.as_deref(),
+        ))
+    }
+}
+
+/// Copy `svg`, passing the start tags and the content of the stylesheets through
+/// `tag` and `style`, which write them to the output.
+pub(crate) fn rewrite_with(
+    svg: &str,
+    mut tag: impl FnMut(&str, &mut String),
+    mut style: impl FnMut(&str, &mut String),
+) -> String {
+    let mut out = String::with_capacity(svg.len() + svg.len() / 8);
+    let mut rest = svg;
+    let mut in_style = false;
+    while let Some(start) = rest.find('<') {
+        let text = &rest[..start];
+        if in_style {
+            style(text, &mut out);
+        } else {
+            out += text;
+        }
+        rest = &rest[start..];
+
+        let end = if rest.starts_with("<!--") {
+            rest.find("-->").map(|i| i + 3)
+        } else if rest.starts_with("<![CDATA[") {
+            rest.find("]]>").map(|i| i + 3)
+        } else {
+            tag_end(rest)
+        };
+        let Some(end) = end else {
+            break;
+        };
+        let current = &rest[..end];
+        if let Some(cdata) = current
+            .strip_prefix("<![CDATA[")
+            .and_then(|t| t.strip_suffix("]]>"))
+            .filter(|_| in_style)
+        {
+            out += "<![CDATA[";
+            style(cdata, &mut out);
+            out += "]]>";
+        } else if current.starts_with("<!")
+            || current.starts_with("<?")
+            || current.starts_with("</")
+        {
+            out += current;
+            in_style &= !current.starts_with("</style");
+        } else {
+            in_style = tag_name(current) == "style" && !current.ends_with("/>");
+            tag(current, &mut out);
+        }
+        rest = &rest[end..];
+    }
+    out += rest;
+    out
+}
+
+/// Prefix the ids of `svg` and embed its fonts, adding the `text_fonts` stylesheet
+/// at the start of the root element.
+fn rewrite(
+    svg: &str,
+    prefix: Option<&str>,
+    resource_path: Option<&Path>,
+    text_fonts: Option<&str>,
+) -> String {
+    let mut text_fonts = text_fonts.filter(|css| !css.is_empty());
+    rewrite_with(
+        svg,
+        |tag, out| {
+            rewrite_tag(tag, prefix, out);
+            if tag_name(tag) == "svg"
+                && !tag.ends_with("/>")
+                && let Some(css) = text_fonts.take()
+            {
+                *out += "<style type=\"text/css\">";
+                *out += css;
+                *out += "</style>";
+            }
+        },
+        |css, out| *out += &rewrite_style(css, prefix, resource_path),
+    )
+}
+
+/// Index just past the `>` closing the tag `rest` starts with, skipping quoted values.
+fn tag_end(rest: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in rest.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            (None, '>') => return Some(i + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+pub(crate) fn tag_name(tag: &str) -> &str {
+    let tag = &tag[1..];
+    let end = tag
+        .find(|c: char| c.is_whitespace() || c == '/' || c == '>')
+        .unwrap_or(tag.len());
+    &tag[..end]
+}
+
+fn rewrite_tag(tag: &str, prefix: Option<&str>, out: &mut String) {
+    let Some(prefix) = prefix else {
+        *out += tag;
+        return;
+    };
+    let mut rest = tag;
+    // Each attribute value is copied up to its opening quote, then rewritten.
+    while let Some(eq) = rest.find('=') {
+        let name = rest[..eq]
+            .trim_end()
+            .rsplit(|c: char| c.is_whitespace())
+            .next()
+            .unwrap_or("");
+        let after = &rest[eq + 1..];
+        let Some(open) = after.find(['"', '\'']) else {
+            break;
+        };
+        let quote = &after[open..open + 1];
+        let value_start = eq + 1 + open + 1;
+        let Some(len) = rest[value_start..].find(quote) else {
+            break;
+        };
+        let value = &rest[value_start..value_start + len];
+        *out += &rest[..value_start];
+        match name {
+            "id" => {
+                *out += prefix;
+                *out += value;
+            }
+            "href" | "xlink:href" if value.starts_with('#') => {
+                *out += "#";
+                *out += prefix;
+                *out += &value[1..];
+            }
+            _ => *out += &prefix_urls(value, prefix),
+        }
+        rest = &rest[value_start + len..];
+    }
+    *out += rest;
+}
+
+/// The attributes of a start tag, values left escaped.
+pub(crate) fn attributes(tag: &str) -> Vec<(String, String)> {
+    let mut attributes = vec![];
+    let mut rest = &tag[1 + tag_name(tag).len()..];
+    while let Some(eq) = rest.find('=') {
+        let name = rest[..eq].trim();
+        let after = rest[eq + 1..].trim_start();
+        let Some(quote) = after.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+            break;
+        };
+        let Some(len) = after[1..].find(quote) else {
+            break;
+        };
+        attributes.push((name.to_string(), after[1..1 + len].to_string()));
+        rest = &after[len + 2..];
+    }
+    attributes
+}
+
+/// The classes of an element, in `data-class` with `svg_html5`.
+pub(crate) fn classes(attributes: &[(String, String)]) -> Option<&str> {
+    let get = |name: &str| {
+        attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    };
+    get("class").or_else(|| get("data-class"))
+}
+
+pub(crate) fn set(attributes: &mut Vec<(String, String)>, name: &str, value: String) {
+    match attributes.iter_mut().find(|(n, _)| n == name) {
+        Some((_, v)) => *v = value,
+        None => attributes.push((name.to_string(), value)),
+    }
+}
+
+/// Write the start tag `tag` again with `attributes`, whose values must be escaped.
+pub(crate) fn write_tag(tag: &str, attributes: &[(String, String)], out: &mut String) {
+    *out += "<";
+    *out += tag_name(tag);
+    for (name, value) in attributes {
+        *out += &format!(" {}=\"{}\"", name, value);
+    }
+    *out += if tag.ends_with("/>") { "/>" } else { ">" };
+}
+
+/// Escape `value` for an XML attribute.
+pub(crate) fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped += "&amp;",
+            '<' => escaped += "&lt;",
+            '>' => escaped += "&gt;",
+            '"' => escaped += "&quot;",
+            '\'' => escaped += "&apos;",
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Prefix the fragment of every `url(#...)` reference.
+fn prefix_urls(value: &str, prefix: &str) -> String {
+    value.replace("url(#", &format!("url(#{}", prefix))
+}
+
+fn rewrite_style(css: &str, prefix: Option<&str>, resource_path: Option<&Path>) -> String {
+    let css = match resource_path {
+        Some(path) => embed_fonts(css, path),
+        None => css.to_string(),
+    };
+    match prefix {
+        Some(prefix) => prefix_urls(&css, prefix),
+        None => css,
+    }
+}
+
+/// Replace `@import url(".../<Font>.css");` with the `@font-face` rules of
+/// `<resource>/<Font>.css`, leaving the imports of unknown fonts alone.
+///
+/// The rules go at the end of the stylesheet, as any `@import` left after them
+/// would be ignored.
+fn embed_fonts(css: &str, resource_path: &Path) -> String {
+    let mut out = String::with_capacity(css.len());
+    let mut font_faces = String::new();
+    let mut rest = css;
+    while let Some(start) = rest.find("@import") {
+        out += &rest[..start];
+        let import = &rest[start..];
+        let Some(end) = import.find(';').map(|i| i + 1) else {
+            break;
+        };
+        let url = import[..end]
+            .trim_start_matches("@import")
+            .trim()
+            .trim_end_matches(';')
+            .trim()
+            .trim_start_matches("url(")
+            .trim_end_matches(')')
+            .trim_matches(['"', '\'']);
+        let font = url
+            .rsplit('/')
+            .next()
+            .and_then(|file| file.strip_suffix(".css"))
+            .filter(|font| !font.is_empty() && !font.contains(['\\', '.']));
+        match font
+            .and_then(|font| fs::read_to_string(resource_path.join(format!("{}.css", font))).ok())
+        {
+            Some(font_face) => font_faces += font_face.trim(),
+            None => out += &import[..end],
+        }
+        rest = &import[end..];
+    }
+    out += rest;
+    out += &font_faces;
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prefixed(svg: &str) -> String {
+        rewrite(svg, Some("p-"), None, None)
+    }
+
+    #[test]
+    fn quoted_gt_does_not_end_a_tag() {
+        let tag = r#"<g data-a="x>y" data-b='1>2'>"#;
+        assert_eq!(tag_end(&format!("{}<rect/>", tag)), Some(tag.len()));
+        assert_eq!(tag_end(r#"<g data-a="x>y"#), None);
+        assert_eq!(
+            prefixed(r#"<g data-a="x>y" id="a"><rect id="b"/></g>"#),
+            r#"<g data-a="x>y" id="p-a"><rect id="p-b"/></g>"#
+        );
+    }
+
+    #[test]
+    fn self_closing_tags() {
+        assert_eq!(tag_name("<use/>"), "use");
+        assert_eq!(
+            prefixed(r##"<use xlink:href="#a"/><use href='#b' /><style/><rect id="c"/>"##),
+            r##"<use xlink:href="#p-a"/><use href='#p-b' /><style/><rect id="p-c"/>"##
+        );
+    }
+
+    #[test]
+    fn id_prefix() {
+        let svg = r##"<svg id="s"><style type="text/css">g.a{fill:url(#grad);}</style><defs><symbol id="E050"/></defs><g id="n1" class="note" clip-path="url(#clip)"><use xlink:href="#E050" href="#E050"/><a href="https://example.org/#top"/></g></svg>"##;
+        assert_eq!(
+            prefixed(svg),
+            r##"<svg id="p-s"><style type="text/css">g.a{fill:url(#p-grad);}</style><defs><symbol id="p-E050"/></defs><g id="p-n1" class="note" clip-path="url(#p-clip)"><use xlink:href="#p-E050" href="#p-E050"/><a href="https://example.org/#top"/></g></svg>"##
+        );
+        assert_eq!(rewrite(svg, None, None, None), svg);
+    }
+
+    #[test]
+    fn embedded_fonts() {
+        let dir = std::env::temp_dir().join(format!("verovior-svg-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("Leipzig.css"),
+            "@font-face{font-family:Leipzig;}\n",
+        )
+        .unwrap();
+        let svg = r#"<svg><style><![CDATA[@import url("https://www.verovio.org/Leipzig.css");@import url("https://x.org/Other.css");]]></style></svg>"#;
+        let out = rewrite(svg, None, Some(&dir), Some("@font-face{font-family:Text;}"));
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            out,
+            r#"<svg><style type="text/css">@font-face{font-family:Text;}</style><style><![CDATA[@import url("https://x.org/Other.css");@font-face{font-family:Leipzig;}]]></style></svg>"#
+        );
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(escape("M0 0<\"&'>"), "M0 0&lt;&quot;&amp;&apos;&gt;");
+    }
+}
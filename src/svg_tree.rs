@@ -0,0 +1,86 @@
+//! Parsing verovio's SVG with usvg, for the `raster` and `pdf` features.
+
+use crate::woff2;
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use usvg::{PostProcessingSteps, TreeParsing, TreePostProc, fontdb};
+
+/// Verovio units, tenths of a millimetre, per inch.
+pub(crate) const UNITS_PER_INCH: f32 = 254.0;
+
+/// Fonts for the resource path used last, loading system fonts takes a while.
+static FONTS: Mutex<Option<(PathBuf, Arc<fontdb::Database>)>> = Mutex::new(None);
+
+/// Parse an SVG produced by verovio, converting its text to paths with the fonts
+/// embedded in the stylesheets of the resource path and those installed on the system.
+pub(crate) fn parse(svg: &str, resource_path: &Path) -> anyhow::Result<usvg::Tree> {
+    let options = usvg::Options {
+        resources_dir: Some(resource_path.to_path_buf()),
+        ..Default::default()
+    };
+    let mut tree = usvg::Tree::from_str(svg, &options)?;
+    tree.postprocess(PostProcessingSteps::default(), &fonts(resource_path));
+    Ok(tree)
+}
+
+/// Verovio units per SVG pixel, which is one unit at a `scale` of 100.
+pub(crate) fn units_per_svg_px(scale: i32) -> f32 {
+    100.0 / scale.max(1) as f32
+}
+
+/// The fonts of the resource path and of the system, loaded once per resource path.
+pub(crate) fn fonts(resource_path: &Path) -> Arc<fontdb::Database> {
+    let mut fonts = FONTS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((path, db)) = fonts.as_ref()
+        && path == resource_path
+    {
+        return db.clone();
+    }
+    let mut db = fontdb::Database::new();
+    // Verovio ships its fonts as WOFF2 in `<Font>.css`, which fontdb cannot read.
+    for font in css_fonts(resource_path) {
+        db.load_font_data(font);
+    }
+    db.load_fonts_dir(resource_path);
+    db.load_system_fonts();
+    let db = Arc::new(db);
+    *fonts = Some((resource_path.to_path_buf(), db.clone()));
+    db
+}
+
+/// The fonts embedded as data URLs in the stylesheets of the resource path, decoded
+/// to TrueType or OpenType. Fonts that cannot be decoded are skipped.
+fn css_fonts(resource_path: &Path) -> Vec<Vec<u8>> {
+    let Ok(entries) = fs::read_dir(resource_path) else {
+        return vec![];
+    };
+    let mut fonts = vec![];
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().is_none_or(|e| e != "css") {
+            continue;
+        }
+        let Ok(css) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let mut rest = css.as_str();
+        while let Some(start) = rest.find("base64,") {
+            rest = &rest[start + "base64,".len()..];
+            let end = rest.find(['"', '\'', ')']).unwrap_or(rest.len());
+            let data: String = rest[..end].split_whitespace().collect();
+            rest = &rest[end..];
+            let Ok(data) = BASE64_STANDARD.decode(data) else {
+                continue;
+            };
+            if data.starts_with(b"wOF2") {
+                fonts.extend(woff2::decode(&data).ok());
+            } else {
+                fonts.push(data);
+            }
+        }
+    }
+    fonts
+}
@@ -0,0 +1,464 @@
+//! Finding the files of the text fonts an SVG uses, to embed them.
+
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use std::fs;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// CSS generic families, which the viewer resolves itself.
+const GENERIC_FAMILIES: [&str; 6] = [
+    "serif",
+    "sans-serif",
+    "monospace",
+    "cursive",
+    "fantasy",
+    "system-ui",
+];
+
+/// The names and style of a font, read from its `name` and `OS/2` tables.
+#[derive(Debug, Clone, PartialEq)]
+struct Face {
+    families: Vec<String>,
+    weight: u16,
+    italic: bool,
+    /// Whether the `fsType` of the font allows embedding it in a document.
+    embeddable: bool,
+}
+
+/// A TrueType or OpenType font file.
+#[derive(Debug, Clone, PartialEq)]
+struct FontFile {
+    path: PathBuf,
+    face: Face,
+}
+
+/// A font used by a `text` or `tspan`: its `font-family` list, weight and italic flag.
+type UsedFont = (String, u16, bool);
+
+static SYSTEM_FONTS: OnceLock<Vec<FontFile>> = OnceLock::new();
+/// The fonts of the resource path used last.
+static RESOURCE_FONTS: Mutex<Option<(PathBuf, Arc<Vec<FontFile>>)>> = Mutex::new(None);
+
+/// `@font-face` rules embedding the faces of the text fonts `svg` uses.
+///
+/// For each `font-family` of a `text` or `tspan`, the first family found among the
+/// font files of the resource path or of the system is embedded, in the face closest
+/// to the weight and style of the element. SMuFL fonts, which have a stylesheet in the
+/// resource path, generic families and fonts whose `fsType` forbids embedding are
+/// left out.
+pub(crate) fn font_faces(svg: &str, resource_path: &Path) -> String {
+    let used = used_fonts(svg);
+    if used.is_empty() {
+        return String::new();
+    }
+
+    let resource_fonts = resource_fonts(resource_path);
+    let files: Vec<&FontFile> = resource_fonts
+        .iter()
+        .chain(SYSTEM_FONTS.get_or_init(system_fonts))
+        .filter(|file| file.face.embeddable)
+        .collect();
+    let mut embedded: Vec<(&str, &FontFile)> = vec![];
+    for (list, weight, italic) in used {
+        let Some((family, file)) = families(&list).into_iter().find_map(|family| {
+            if resource_path.join(format!("{}.css", family)).exists() {
+                return None;
+            }
+            closest_face(&files, &family, weight, italic).map(|file| (family, file))
+        }) else {
+            continue;
+        };
+        if !embedded
+            .iter()
+            .any(|(f, e)| f.eq_ignore_ascii_case(&family) && e.path == file.path)
+        {
+            let name = file
+                .face
+                .families
+                .iter()
+                .find(|f| f.eq_ignore_ascii_case(&family))
+                .map_or("", String::as_str);
+            embedded.push((name, file));
+        }
+    }
+
+    let mut css = String::new();
+    for (family, file) in embedded {
+        let Ok(data) = fs::read(&file.path) else {
+            continue;
+        };
+        let (mime, format) = if data.starts_with(b"OTTO") {
+            ("font/otf", "opentype")
+        } else {
+            ("font/ttf", "truetype")
+        };
+        css += &format!(
+            "@font-face{{font-family:\"{}\";font-weight:{};font-style:{};src:url(data:{};base64,{}) format(\"{}\");}}",
+            family,
+            file.face.weight,
+            if file.face.italic { "italic" } else { "normal" },
+            mime,
+            BASE64_STANDARD.encode(&data),
+            format
+        );
+    }
+    css
+}
+
+/// The fonts of the `text` and `tspan` elements of `svg`, with the family, weight and
+/// style they inherit, each once.
+fn used_fonts(svg: &str) -> Vec<UsedFont> {
+    let Ok(doc) = roxmltree::Document::parse(svg) else {
+        return vec![];
+    };
+    let mut used: Vec<UsedFont> = vec![];
+    for node in doc
+        .descendants()
+        .filter(|n| n.has_tag_name("text") || n.has_tag_name("tspan"))
+    {
+        let inherited = |name| node.ancestors().find_map(|n| n.attribute(name));
+        let Some(list) = inherited("font-family") else {
+            continue;
+        };
+        let weight = match inherited("font-weight").map(str::trim) {
+            Some("bold" | "bolder") => 700,
+            Some("lighter") => 300,
+            Some(weight) => weight.parse().unwrap_or(400),
+            None => 400,
+        };
+        let italic = matches!(inherited("font-style"), Some("italic" | "oblique"));
+        let font = (list.to_string(), weight, italic);
+        if !used.contains(&font) {
+            used.push(font);
+        }
+    }
+    used
+}
+
+/// The file of `family` closest to `weight` and `italic`, the style first.
+fn closest_face<'a>(
+    files: &[&'a FontFile],
+    family: &str,
+    weight: u16,
+    italic: bool,
+) -> Option<&'a FontFile> {
+    files
+        .iter()
+        .filter(|file| file.has_family(family))
+        .min_by_key(|file| {
+            (
+                file.face.italic != italic,
+                file.face.weight.abs_diff(weight),
+            )
+        })
+        .copied()
+}
+
+impl FontFile {
+    fn has_family(&self, family: &str) -> bool {
+        self.face
+            .families
+            .iter()
+            .any(|f| f.eq_ignore_ascii_case(family))
+    }
+}
+
+/// The named families of a `font-family` value, unquoted, without the generic ones.
+fn families(value: &str) -> Vec<String> {
+    value
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+        .split(',')
+        .map(|family| family.trim().trim_matches(['"', '\'']).trim())
+        .filter(|family| {
+            !family.is_empty()
+                && !family.contains(['"', '\\', ';', '{', '}'])
+                && !GENERIC_FAMILIES.contains(&family.to_ascii_lowercase().as_str())
+        })
+        .map(String::from)
+        .collect()
+}
+
+fn resource_fonts(resource_path: &Path) -> Arc<Vec<FontFile>> {
+    let mut fonts = RESOURCE_FONTS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((path, files)) = fonts.as_ref()
+        && path == resource_path
+    {
+        return files.clone();
+    }
+    let mut files = vec![];
+    find_font_files(resource_path, &mut files);
+    let files = Arc::new(files);
+    *fonts = Some((resource_path.to_path_buf(), files.clone()));
+    files
+}
+
+fn system_fonts() -> Vec<FontFile> {
+    let mut files = vec![];
+    for dir in system_font_dirs() {
+        find_font_files(&dir, &mut files);
+    }
+    files
+}
+
+fn system_font_dirs() -> Vec<PathBuf> {
+    let var = |name: &str| {
+        std::env::var_os(name)
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+    };
+    let home = var("HOME");
+    if cfg!(windows) {
+        let mut dirs: Vec<PathBuf> = var("WINDIR")
+            .map(|dir| dir.join("Fonts"))
+            .into_iter()
+            .collect();
+        dirs.extend(var("LOCALAPPDATA").map(|dir| dir.join("Microsoft/Windows/Fonts")));
+        dirs
+    } else if cfg!(target_os = "macos") {
+        let mut dirs = vec![
+            PathBuf::from("/System/Library/Fonts"),
+            PathBuf::from("/Library/Fonts"),
+        ];
+        dirs.extend(home.map(|home| home.join("Library/Fonts")));
+        dirs
+    } else {
+        let mut dirs = vec![
+            PathBuf::from("/usr/share/fonts"),
+            PathBuf::from("/usr/local/share/fonts"),
+        ];
+        dirs.extend(
+            var("XDG_DATA_HOME")
+                .or_else(|| home.as_ref().map(|home| home.join(".local/share")))
+                .map(|dir| dir.join("fonts")),
+        );
+        dirs.extend(home.map(|home| home.join(".fonts")));
+        dirs
+    }
+}
+
+/// Add the `.ttf` and `.otf` files under `dir` that can be read to `files`.
+fn find_font_files(dir: &Path, files: &mut Vec<FontFile>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if entry.file_type().is_ok_and(|t| t.is_dir()) {
+            find_font_files(&path, files);
+            continue;
+        }
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        if !matches!(extension.as_deref(), Some("ttf" | "otf")) {
+            continue;
+        }
+        if let Ok(file) = fs::File::open(&path)
+            && let Ok(face) = read_face(BufReader::new(file))
+        {
+            files.push(FontFile { path, face });
+        }
+    }
+}
+
+/// The face of a font, read from its `name` and `OS/2` tables without loading the rest
+/// of the file.
+fn read_face(mut file: impl Read + Seek) -> io::Result<Face> {
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not a TrueType or OpenType font",
+        )
+    };
+    let mut header = [0; 12];
+    file.read_exact(&mut header)?;
+    if !matches!(&header[..4], [0, 1, 0, 0] | b"OTTO" | b"true") {
+        return Err(invalid());
+    }
+    let mut records = vec![0; u16_at(&header, 4) as usize * 16];
+    file.read_exact(&mut records)?;
+    let mut table = |tag: &[u8]| -> io::Result<Option<Vec<u8>>> {
+        let Some(record) = records.chunks_exact(16).find(|r| &r[..4] == tag) else {
+            return Ok(None);
+        };
+        let length = u32_at(record, 12).min(1 << 20);
+        file.seek(SeekFrom::Start(u32_at(record, 8) as u64))?;
+        let mut data = vec![0; length as usize];
+        file.read_exact(&mut data)?;
+        Ok(Some(data))
+    };
+
+    let name = table(b"name")?.ok_or_else(invalid)?;
+    let (weight, italic, fs_type) = match table(b"OS/2")? {
+        Some(os2) if os2.len() >= 64 => {
+            (u16_at(&os2, 4), u16_at(&os2, 62) & 1 != 0, u16_at(&os2, 8))
+        }
+        _ => (400, false, 0),
+    };
+    // Restricted licence embedding, unless preview & print or editable embedding is also
+    // allowed, and bitmap embedding only.
+    let embeddable = fs_type & 0x000e != 0x0002 && fs_type & 0x0200 == 0;
+    let mut families: Vec<String> = vec![];
+    let strings = u16_at(&name, 4) as usize;
+    for record in name
+        .get(6..)
+        .unwrap_or_default()
+        .chunks_exact(12)
+        .take(u16_at(&name, 2) as usize)
+    {
+        // The family and the typographic family.
+        if !matches!(u16_at(record, 6), 1 | 16) {
+            continue;
+        }
+        let start = strings + u16_at(record, 10) as usize;
+        let Some(bytes) = name.get(start..start + u16_at(record, 8) as usize) else {
+            continue;
+        };
+        let family = match u16_at(record, 0) {
+            0 | 3 => String::from_utf16_lossy(
+                &bytes
+                    .chunks_exact(2)
+                    .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                    .collect::<Vec<_>>(),
+            ),
+            1 => bytes.iter().map(|b| *b as char).collect(),
+            _ => continue,
+        };
+        if !family.is_empty() && !families.contains(&family) {
+            families.push(family);
+        }
+    }
+    if families.is_empty() {
+        return Err(invalid());
+    }
+    Ok(Face {
+        families,
+        weight,
+        italic,
+        embeddable,
+    })
+}
+
+fn u16_at(data: &[u8], offset: usize) -> u16 {
+    data.get(offset..offset + 2)
+        .map_or(0, |b| u16::from_be_bytes([b[0], b[1]]))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    data.get(offset..offset + 4)
+        .map_or(0, |b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// A font with only a `name` table naming `family` and an `OS/2` table.
+    fn font(family: &str, weight: u16, italic: bool) -> Vec<u8> {
+        font_with_fs_type(family, weight, italic, 0)
+    }
+
+    fn font_with_fs_type(family: &str, weight: u16, italic: bool, fs_type: u16) -> Vec<u8> {
+        let utf16: Vec<u8> = family.encode_utf16().flat_map(u16::to_be_bytes).collect();
+        let mut name = vec![];
+        for value in [0u16, 2, 18] {
+            name.extend(value.to_be_bytes());
+        }
+        // platform, encoding, language, name id, length, offset
+        for value in [3u16, 1, 0x409, 1, utf16.len() as u16, 0] {
+            name.extend(value.to_be_bytes());
+        }
+        name.extend(&utf16);
+        let mut os2 = vec![0; 64];
+        os2[4..6].copy_from_slice(&weight.to_be_bytes());
+        os2[8..10].copy_from_slice(&fs_type.to_be_bytes());
+        os2[62..64].copy_from_slice(&(italic as u16).to_be_bytes());
+
+        let mut data = vec![0, 1, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0];
+        let mut offset = 12 + 2 * 16;
+        for (tag, table) in [(b"OS/2", &os2), (b"name", &name)] {
+            data.extend(tag);
+            data.extend([0; 4]);
+            data.extend((offset as u32).to_be_bytes());
+            data.extend((table.len() as u32).to_be_bytes());
+            offset += table.len();
+        }
+        data.extend(&os2);
+        data.extend(&name);
+        data
+    }
+
+    #[test]
+    fn faces() {
+        let face = read_face(Cursor::new(font("Liberation Serif", 700, true))).unwrap();
+        assert_eq!(
+            face,
+            Face {
+                families: vec!["Liberation Serif".to_string()],
+                weight: 700,
+                italic: true,
+                embeddable: true,
+            }
+        );
+        let embeddable = |fs_type| {
+            read_face(Cursor::new(font_with_fs_type("F", 400, false, fs_type)))
+                .unwrap()
+                .embeddable
+        };
+        assert!(embeddable(0x0004));
+        assert!(embeddable(0x0008));
+        assert!(!embeddable(0x0002));
+        assert!(!embeddable(0x0200));
+        assert!(read_face(Cursor::new(b"wOF2 and more bytes".to_vec())).is_err());
+    }
+
+    #[test]
+    fn family_lists() {
+        assert_eq!(
+            families("Times, &quot;Liberation Serif&quot;, 'DejaVu Serif',serif"),
+            ["Times", "Liberation Serif", "DejaVu Serif"]
+        );
+        assert!(families("serif, Sans-Serif").is_empty());
+    }
+
+    #[test]
+    fn used_fonts_inherit_weight_and_style() {
+        let svg = r#"<svg><g font-weight="bold"><text font-family="A, serif">a<tspan font-style="italic">b</tspan><tspan font-weight="normal">c</tspan></text></g><text font-family="A, serif" font-weight="700">d</text><text>e</text></svg>"#;
+        assert_eq!(
+            used_fonts(svg),
+            [
+                ("A, serif".to_string(), 700, false),
+                ("A, serif".to_string(), 700, true),
+                ("A, serif".to_string(), 400, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn embeds_the_faces_used() {
+        let dir = std::env::temp_dir().join(format!("verovior-text-fonts-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("Regular.ttf"), font("Text Test Font", 400, false)).unwrap();
+        fs::write(dir.join("Bold.ttf"), font("Text Test Font", 700, false)).unwrap();
+        fs::write(dir.join("Italic.ttf"), font("Text Test Font", 400, true)).unwrap();
+        fs::write(
+            dir.join("Restricted.ttf"),
+            font_with_fs_type("Restricted Test Font", 400, false, 0x0002),
+        )
+        .unwrap();
+        fs::write(dir.join("Leipzig.css"), "").unwrap();
+        let svg = r#"<svg><text font-family="Missing, 'text test font', serif" font-weight="600">a<tspan font-family="Leipzig">b</tspan></text><text font-family="Restricted Test Font">c</text></svg>"#;
+        let css = font_faces(svg, &dir);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(css.matches("@font-face").count(), 1);
+        assert!(css.contains("font-family:\"Text Test Font\";font-weight:700;font-style:normal"));
+        assert!(!css.contains("Leipzig"));
+        assert!(!css.contains("Restricted"));
+    }
+}
@@ -0,0 +1,158 @@
+//! Colour themes for the SVG output, such as a dark mode.
+
+use crate::{AllOptions, VerovioToolkit};
+
+/// The colours of the rendered score, turned into the `svg_css` option by [`Theme::css`].
+///
+/// Printing always uses the light colours, so a score shown in dark mode prints as usual.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    /// Colour of the notation and text.
+    pub foreground: String,
+    /// Colour behind the page, `None` leaving it transparent.
+    ///
+    /// It is set as the CSS background of the root `svg`, which browsers paint when the
+    /// SVG is shown on its own or inlined in HTML, but not when it is used as an `<img>`
+    /// or drawn by resvg. For PNG output, pass the colour as
+    /// `RasterOptions::background` instead.
+    pub background: Option<String>,
+    /// Colour of the elements with the `highlight` class, such as added by [`crate::Styling`].
+    pub highlight: String,
+    /// Colour of the staff lines.
+    pub staff_lines: String,
+}
+
+impl Theme {
+    /// Black on a transparent page, as verovio renders by default.
+    pub fn light() -> Theme {
+        Theme {
+            foreground: "black".into(),
+            background: None,
+            highlight: "#d62728".into(),
+            staff_lines: "black".into(),
+        }
+    }
+
+    /// Light notation on a dark page.
+    pub fn dark() -> Theme {
+        Theme {
+            foreground: "#e8e8e8".into(),
+            background: Some("#1e1e1e".into()),
+            highlight: "#ff7f50".into(),
+            staff_lines: "#9a9a9a".into(),
+        }
+    }
+
+    /// The stylesheet for the `svg_css` option.
+    ///
+    /// Verovio draws lines with `currentColor` and leaves the fill of glyphs unset, so the
+    /// foreground is set as the colour and fill of the score, for its elements to inherit.
+    /// The rules only select the `svg.definition-scale` element verovio wraps the score in,
+    /// its descendants and the root `svg` holding it, so that an SVG inlined in HTML leaves
+    /// the rest of the page alone.
+    pub fn css(&self) -> String {
+        let light = Theme::light();
+        let mut css = rules(self);
+        css += &format!("@media print {{ {} }}", rules(&light));
+        css
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::light()
+    }
+}
+
+/// The element verovio wraps the score in, inside the root `svg`.
+const SCORE: &str = "svg.definition-scale";
+/// The root `svg`. A CSS background on the nested one is not painted.
+const ROOT: &str = "svg:has(> svg.definition-scale)";
+/// Delimit the theme in the `svg_css` option, so that it can be replaced.
+const THEME_START: &str = "/* theme */";
+const THEME_END: &str = "/* end of theme */";
+
+fn rules(theme: &Theme) -> String {
+    format!(
+        "{root} {{ background-color: {bg}; }} \
+         {score} {{ color: {fg}; fill: currentColor; }} \
+         {score} g.staff > path {{ stroke: {staff}; }} \
+         {score} .highlight, {score} .highlight * {{ color: {hl}; fill: {hl}; }} ",
+        root = ROOT,
+        score = SCORE,
+        fg = theme.foreground,
+        bg = theme.background.as_deref().unwrap_or("transparent"),
+        staff = theme.staff_lines,
+        hl = theme.highlight,
+    )
+}
+
+impl VerovioToolkit {
+    /// Render the SVG output with the colours of `theme`.
+    ///
+    /// The theme goes in front of the `svg_css` option, replacing a theme set before and
+    /// keeping the other rules, which come later and so take precedence over it.
+    pub fn set_theme(&self, theme: &Theme) -> anyhow::Result<bool> {
+        let css = self.get_options()?.svg_css.unwrap_or_default();
+        let options = AllOptions {
+            svg_css: Some(format!(
+                "{} {}{} {}",
+                THEME_START,
+                theme.css(),
+                THEME_END,
+                without_theme(&css)
+            )),
+            ..Default::default()
+        };
+        self.set_options(&options)
+    }
+}
+
+/// `css` without the theme [`VerovioToolkit::set_theme`] added to it.
+fn without_theme(css: &str) -> &str {
+    css.trim_start()
+        .strip_prefix(THEME_START)
+        .and_then(|rest| rest.split_once(THEME_END))
+        .map_or(css, |(_, rest)| rest.trim_start())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn css() {
+        let css = Theme::dark().css();
+        assert!(css.starts_with(
+            "svg:has(> svg.definition-scale) { background-color: #1e1e1e; } \
+             svg.definition-scale { color: #e8e8e8; fill: currentColor; }"
+        ));
+        let (screen, print) = css.split_once("@media print").unwrap();
+        assert!(screen.contains("g.staff > path { stroke: #9a9a9a; }"));
+        assert!(screen.contains(".highlight * { color: #ff7f50; fill: #ff7f50; }"));
+        assert!(print.contains("background-color: transparent;"));
+        assert!(print.contains("color: black;"));
+        assert!(!print.contains("#1e1e1e"));
+    }
+
+    #[test]
+    fn removes_the_theme() {
+        let css = format!(
+            "{} {}{} g.note {{ fill: red; }}",
+            THEME_START,
+            Theme::dark().css(),
+            THEME_END
+        );
+        assert_eq!(without_theme(&css), "g.note { fill: red; }");
+        assert_eq!(
+            without_theme(&format!("  {}", css)),
+            "g.note { fill: red; }"
+        );
+        assert_eq!(
+            without_theme("g.note { fill: red; }"),
+            "g.note { fill: red; }"
+        );
+        // An unterminated theme is kept as it is.
+        assert_eq!(without_theme("/* theme */ svg {}"), "/* theme */ svg {}");
+    }
+}
@@ -0,0 +1,137 @@
+//! Rendering the opening measures of a piece as a small preview.
+
+use crate::geometry;
+use crate::svg::{attributes, rewrite_with, set, tag_name, write_tag};
+use crate::{AllOptions, VerovioToolkit};
+use anyhow::anyhow;
+
+/// Page margins of a thumbnail, in verovio units.
+const MARGIN: i32 = 50;
+
+/// The font family verovio gives text, for scores without any.
+const DEFAULT_FONT_FAMILY: &str = "Times,serif";
+
+impl VerovioToolkit {
+    /// Render the first measures of the loaded document as a single cropped system,
+    /// scaled to `width_px`, optionally with a title above it.
+    ///
+    /// The measures are taken from the first `mdiv`, fewer if it has fewer. The title is
+    /// set in the text font of the score. The selection and options in place before are
+    /// restored.
+    pub fn render_thumbnail(
+        &self,
+        n_measures: u32,
+        width_px: u32,
+        title: Option<&str>,
+    ) -> anyhow::Result<String> {
+        if n_measures == 0 || width_px == 0 {
+            return Err(anyhow!(
+                "a thumbnail needs at least one measure and one pixel"
+            ));
+        }
+        let options = AllOptions {
+            breaks: Some("none".into()),
+            adjust_page_width: Some(true),
+            adjust_page_height: Some(true),
+            header: Some("none".into()),
+            footer: Some("none".into()),
+            page_margin_top: Some(MARGIN),
+            page_margin_bottom: Some(MARGIN),
+            page_margin_left: Some(MARGIN),
+            page_margin_right: Some(MARGIN),
+            ..Default::default()
+        };
+        let n_measures = n_measures.min(self.first_mdiv_measures()?);
+        if n_measures == 0 {
+            return Err(anyhow!("the document has no measures"));
+        }
+        let selection = serde_json::json!({ "measureRange": format!("1-{}", n_measures) });
+        let svg = self
+            .render_selection(&selection.to_string(), &options)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("the document has no measures"))?;
+
+        let page = geometry::parse(&svg)?;
+        let (width, height) = (page.width, page.height);
+        // Three staff spaces, a staff space being two units.
+        let unit = self.get_options()?.unit.unwrap_or(9.0);
+        let font_size = 6.0 * unit * self.get_scale()? as f64 / 100.0;
+        let band = if title.is_some() {
+            font_size * 1.6
+        } else {
+            0.0
+        };
+        let total_height = height + band;
+
+        let mut root = true;
+        let mut font_family = None;
+        let score = rewrite_with(
+            &svg,
+            |tag, out| {
+                if font_family.is_none() && tag_name(tag) == "text" {
+                    font_family = attributes(tag)
+                        .into_iter()
+                        .find(|(n, _)| n == "font-family")
+                        .map(|(_, v)| v);
+                }
+                if !std::mem::take(&mut root) || tag_name(tag) != "svg" {
+                    *out += tag;
+                    return;
+                }
+                let mut attributes = attributes(tag);
+                set(&mut attributes, "y", band.to_string());
+                set(&mut attributes, "width", width.to_string());
+                set(&mut attributes, "height", height.to_string());
+                if !attributes.iter().any(|(n, _)| n == "viewBox") {
+                    set(
+                        &mut attributes,
+                        "viewBox",
+                        format!("0 0 {} {}", width, height),
+                    );
+                }
+                write_tag(tag, &attributes, out);
+            },
+            |css, out| *out += css,
+        );
+
+        let mut thumbnail = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}px\" height=\"{}px\" viewBox=\"0 0 {} {}\">",
+            width_px,
+            (total_height * width_px as f64 / width).round(),
+            width,
+            total_height
+        );
+        if let Some(title) = title {
+            let title = title
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;");
+            thumbnail += &format!(
+                "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\" font-family=\"{}\" font-size=\"{}\">{}</text>",
+                width / 2.0,
+                font_size * 1.1,
+                font_family.as_deref().unwrap_or(DEFAULT_FONT_FAMILY),
+                font_size,
+                title
+            );
+        }
+        thumbnail += &score;
+        thumbnail += "</svg>";
+        Ok(thumbnail)
+    }
+
+    /// The number of measures in the first `mdiv` holding music.
+    fn first_mdiv_measures(&self) -> anyhow::Result<u32> {
+        let mei = self.get_mei("")?;
+        let doc = roxmltree::Document::parse(&mei)?;
+        let mdiv = doc.descendants().find(|n| {
+            n.has_tag_name("mdiv") && !n.descendants().skip(1).any(|d| d.has_tag_name("mdiv"))
+        });
+        Ok(mdiv.map_or(0, |mdiv| {
+            mdiv.descendants()
+                .filter(|n| n.has_tag_name("measure"))
+                .count() as u32
+        }))
+    }
+}
@@ -0,0 +1,676 @@
+//! Decoding of WOFF2 fonts, the format verovio embeds in its CSS, to the TrueType
+//! or OpenType data fontdb reads.
+
+use anyhow::{anyhow, bail};
+use std::io::Read;
+
+const SIGNATURE: u32 = 0x774f_4632;
+const HEADER_SIZE: usize = 48;
+const GLYF: [u8; 4] = *b"glyf";
+const LOCA: [u8; 4] = *b"loca";
+const HMTX: [u8; 4] = *b"hmtx";
+
+/// Tags of the tables with a known tag index, in the order of the specification.
+const KNOWN_TAGS: [&[u8; 4]; 63] = [
+    b"cmap", b"head", b"hhea", b"hmtx", b"maxp", b"name", b"OS/2", b"post", b"cvt ", b"fpgm",
+    b"glyf", b"loca", b"prep", b"CFF ", b"VORG", b"EBDT", b"EBLC", b"gasp", b"hdmx", b"kern",
+    b"LTSH", b"PCLT", b"VDMX", b"vhea", b"vmtx", b"BASE", b"GDEF", b"GPOS", b"GSUB", b"EBSC",
+    b"JSTF", b"MATH", b"CBDT", b"CBLC", b"COLR", b"CPAL", b"SVG ", b"sbix", b"acnt", b"avar",
+    b"bdat", b"bloc", b"bsln", b"cvar", b"fdsc", b"feat", b"fmtx", b"fvar", b"gvar", b"hsty",
+    b"just", b"lcar", b"mort", b"morx", b"opbd", b"prop", b"trak", b"Zapf", b"Silf", b"Glat",
+    b"Gloc", b"Feat", b"Sill",
+];
+
+struct TableEntry {
+    tag: [u8; 4],
+    transformed: bool,
+    orig_length: usize,
+    /// Length of the table in the decompressed stream.
+    length: usize,
+}
+
+/// Decode a WOFF2 font to an sfnt. Font collections are not supported.
+pub(crate) fn decode(woff2: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut header = Reader::new(woff2);
+    if header.u32()? != SIGNATURE {
+        bail!("not a WOFF2 font");
+    }
+    let flavor = header.u32()?;
+    if flavor == u32::from_be_bytes(*b"ttcf") {
+        bail!("WOFF2 font collections are not supported");
+    }
+    header.skip(4)?;
+    let num_tables = header.u16()?;
+    header.skip(6)?;
+    let compressed_size = header.u32()? as usize;
+    header.pos = HEADER_SIZE;
+
+    let mut entries = vec![];
+    for _ in 0..num_tables {
+        let flags = header.u8()?;
+        let tag = match (flags & 0x3f) as usize {
+            63 => header.u32()?.to_be_bytes(),
+            index => *KNOWN_TAGS[index],
+        };
+        let version = flags >> 6;
+        // For glyf and loca version 0 is their transform, for other tables the null transform.
+        let transformed = if tag == GLYF || tag == LOCA {
+            version == 0
+        } else {
+            version != 0
+        };
+        let orig_length = header.base128()? as usize;
+        let length = if transformed {
+            header.base128()? as usize
+        } else {
+            orig_length
+        };
+        entries.push(TableEntry {
+            tag,
+            transformed,
+            orig_length,
+            length,
+        });
+    }
+
+    // The sfnt table directory has 16 bytes per table and a 16-bit search range.
+    if entries.is_empty() || entries.len() > 4095 {
+        bail!("WOFF2 font has {} tables", entries.len());
+    }
+
+    let compressed = header.bytes(compressed_size)?;
+    let size: u64 = entries.iter().map(|e| e.length as u64).sum();
+    let mut data = vec![];
+    brotli_decompressor::Decompressor::new(compressed, 4096)
+        .take(size)
+        .read_to_end(&mut data)?;
+
+    let mut tables: Vec<([u8; 4], Vec<u8>)> = vec![];
+    let mut offset = 0;
+    let mut glyf = None;
+    for entry in &entries {
+        let table = data
+            .get(offset..offset + entry.length)
+            .ok_or_else(|| anyhow!("WOFF2 table data is truncated"))?;
+        offset += entry.length;
+        match entry.tag {
+            GLYF if entry.transformed => {
+                let decoded = decode_glyf(table)?;
+                tables.push((GLYF, decoded.glyf));
+                tables.push((LOCA, decoded.loca));
+                glyf = Some(decoded.x_mins);
+            }
+            // Rebuilt with glyf.
+            LOCA if entry.transformed => {}
+            HMTX if entry.transformed => tables.push((HMTX, table.to_vec())),
+            _ if entry.transformed => bail!(
+                "unknown WOFF2 transform of table {}",
+                String::from_utf8_lossy(&entry.tag)
+            ),
+            tag => tables.push((tag, table.to_vec())),
+        }
+    }
+    if let Some(hmtx) = entries.iter().find(|e| e.tag == HMTX && e.transformed) {
+        let x_mins = glyf.ok_or_else(|| anyhow!("WOFF2 hmtx transform without glyf"))?;
+        let hhea = table(&tables, b"hhea")?;
+        let num_h_metrics = Reader::at(hhea, 34).u16()? as usize;
+        let index = tables.iter().position(|(tag, _)| *tag == HMTX).unwrap();
+        tables[index].1 = decode_hmtx(&tables[index].1, num_h_metrics, &x_mins)?;
+        if tables[index].1.len() != hmtx.orig_length {
+            bail!("WOFF2 hmtx table has the wrong length");
+        }
+    }
+    Ok(sfnt(flavor, tables))
+}
+
+fn table<'a>(tables: &'a [([u8; 4], Vec<u8>)], tag: &[u8; 4]) -> anyhow::Result<&'a [u8]> {
+    tables
+        .iter()
+        .find(|(t, _)| t == tag)
+        .map(|(_, data)| data.as_slice())
+        .ok_or_else(|| anyhow!("font has no {} table", String::from_utf8_lossy(tag)))
+}
+
+/// Assemble the tables in an sfnt, sorted by tag as the format requires.
+pub(crate) fn sfnt(flavor: u32, mut tables: Vec<([u8; 4], Vec<u8>)>) -> Vec<u8> {
+    tables.sort_by_key(|(tag, _)| *tag);
+    let num_tables = tables.len() as u16;
+    let entry_selector = num_tables.ilog2() as u16;
+    let search_range = (1u16 << entry_selector) * 16;
+    let mut out = vec![];
+    out.extend(flavor.to_be_bytes());
+    out.extend(num_tables.to_be_bytes());
+    out.extend(search_range.to_be_bytes());
+    out.extend(entry_selector.to_be_bytes());
+    out.extend((num_tables * 16 - search_range).to_be_bytes());
+    let mut offset = 12 + 16 * tables.len();
+    for (tag, data) in &tables {
+        out.extend(tag);
+        out.extend(checksum(data).to_be_bytes());
+        out.extend((offset as u32).to_be_bytes());
+        out.extend((data.len() as u32).to_be_bytes());
+        offset += data.len().next_multiple_of(4);
+    }
+    for (_, data) in &tables {
+        out.extend(data);
+        out.resize(out.len().next_multiple_of(4), 0);
+    }
+    out
+}
+
+pub(crate) fn checksum(data: &[u8]) -> u32 {
+    data.chunks(4).fold(0u32, |sum, chunk| {
+        let mut word = [0; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        sum.wrapping_add(u32::from_be_bytes(word))
+    })
+}
+
+struct Glyf {
+    glyf: Vec<u8>,
+    loca: Vec<u8>,
+    /// The left edge of every glyph, which the hmtx transform may leave out.
+    x_mins: Vec<i16>,
+}
+
+/// Rebuild the glyf and loca tables from the transformed glyf table.
+fn decode_glyf(table: &[u8]) -> anyhow::Result<Glyf> {
+    let mut header = Reader::new(table);
+    header.skip(2)?;
+    let option_flags = header.u16()?;
+    let num_glyphs = header.u16()? as usize;
+    let index_format = header.u16()?;
+    let mut sizes = [0; 7];
+    for size in &mut sizes {
+        *size = header.u32()? as usize;
+    }
+    let mut n_contours = Reader::new(header.bytes(sizes[0])?);
+    let mut n_points = Reader::new(header.bytes(sizes[1])?);
+    let mut flags = Reader::new(header.bytes(sizes[2])?);
+    let mut glyphs = Reader::new(header.bytes(sizes[3])?);
+    let mut composites = Reader::new(header.bytes(sizes[4])?);
+    let mut bboxes = Reader::new(header.bytes(sizes[5])?);
+    let mut instructions = Reader::new(header.bytes(sizes[6])?);
+    let overlap_bitmap = if option_flags & 1 != 0 {
+        Some(header.bytes(num_glyphs.div_ceil(8))?)
+    } else {
+        None
+    };
+    let bbox_bitmap = bboxes.bytes(num_glyphs.div_ceil(32) * 4)?;
+    let has_bit = |bitmap: &[u8], i: usize| bitmap[i >> 3] & (0x80 >> (i & 7)) != 0;
+
+    let mut glyf = vec![];
+    let mut offsets = vec![0];
+    let mut x_mins = vec![];
+    for i in 0..num_glyphs {
+        let contours = n_contours.i16()?;
+        let explicit_bbox = has_bit(bbox_bitmap, i);
+        let mut x_min = 0;
+        if contours == 0 {
+            if explicit_bbox {
+                bail!("WOFF2 empty glyph {} has a bounding box", i);
+            }
+        } else if contours < 0 {
+            if !explicit_bbox {
+                bail!("WOFF2 composite glyph {} has no bounding box", i);
+            }
+            let bbox = bboxes.bytes(8)?;
+            x_min = i16::from_be_bytes([bbox[0], bbox[1]]);
+            let (data, has_instructions) = composite(&mut composites)?;
+            glyf.extend(contours.to_be_bytes());
+            glyf.extend(bbox);
+            glyf.extend(data);
+            if has_instructions {
+                let length = glyphs.u255()?;
+                glyf.extend(length.to_be_bytes());
+                glyf.extend(instructions.bytes(length as usize)?);
+            }
+        } else {
+            let mut end_points = vec![];
+            let mut total = 0u16;
+            for _ in 0..contours {
+                total = total
+                    .checked_add(n_points.u255()?)
+                    .ok_or_else(|| anyhow!("WOFF2 glyph {} has too many points", i))?;
+                end_points.push(total.wrapping_sub(1));
+            }
+            let mut points = vec![];
+            let (mut x, mut y) = (0i32, 0i32);
+            for _ in 0..total {
+                let flag = flags.u8()?;
+                let (dx, dy) = triplet(flag & 0x7f, &mut glyphs)?;
+                x = x.wrapping_add(dx);
+                y = y.wrapping_add(dy);
+                points.push((x, y, flag & 0x80 == 0));
+            }
+            let instruction_length = glyphs.u255()?;
+            let bbox = if explicit_bbox {
+                let mut bbox = [0i16; 4];
+                for value in &mut bbox {
+                    *value = bboxes.i16()?;
+                }
+                bbox
+            } else {
+                let (mut x_min, mut y_min, mut x_max, mut y_max) =
+                    (i32::MAX, i32::MAX, i32::MIN, i32::MIN);
+                for &(x, y, _) in &points {
+                    x_min = x_min.min(x);
+                    y_min = y_min.min(y);
+                    x_max = x_max.max(x);
+                    y_max = y_max.max(y);
+                }
+                if points.is_empty() {
+                    [0; 4]
+                } else {
+                    [x_min as i16, y_min as i16, x_max as i16, y_max as i16]
+                }
+            };
+            x_min = bbox[0];
+            glyf.extend(contours.to_be_bytes());
+            for value in bbox {
+                glyf.extend(value.to_be_bytes());
+            }
+            for end in end_points {
+                glyf.extend(end.to_be_bytes());
+            }
+            glyf.extend(instruction_length.to_be_bytes());
+            glyf.extend(instructions.bytes(instruction_length as usize)?);
+            let overlap = overlap_bitmap.is_some_and(|bitmap| has_bit(bitmap, i));
+            simple_points(&points, overlap, &mut glyf);
+        }
+        glyf.resize(glyf.len().next_multiple_of(4), 0);
+        offsets.push(glyf.len());
+        x_mins.push(x_min);
+    }
+
+    let mut loca = vec![];
+    for offset in offsets {
+        if index_format == 0 {
+            let offset = u16::try_from(offset / 2)
+                .map_err(|_| anyhow!("WOFF2 glyf table is too large for short offsets"))?;
+            loca.extend(offset.to_be_bytes());
+        } else {
+            loca.extend((offset as u32).to_be_bytes());
+        }
+    }
+    Ok(Glyf { glyf, loca, x_mins })
+}
+
+/// Read the components of a composite glyph, telling whether it has instructions.
+fn composite<'a>(stream: &mut Reader<'a>) -> anyhow::Result<(&'a [u8], bool)> {
+    const ARGS_ARE_WORDS: u16 = 0x0001;
+    const HAVE_SCALE: u16 = 0x0008;
+    const MORE_COMPONENTS: u16 = 0x0020;
+    const HAVE_XY_SCALE: u16 = 0x0040;
+    const HAVE_TWO_BY_TWO: u16 = 0x0080;
+    const HAVE_INSTRUCTIONS: u16 = 0x0100;
+
+    let start = stream.pos;
+    let mut has_instructions = false;
+    loop {
+        let flags = stream.u16()?;
+        has_instructions |= flags & HAVE_INSTRUCTIONS != 0;
+        let mut size = 2 + if flags & ARGS_ARE_WORDS != 0 { 4 } else { 2 };
+        if flags & HAVE_SCALE != 0 {
+            size += 2;
+        } else if flags & HAVE_XY_SCALE != 0 {
+            size += 4;
+        } else if flags & HAVE_TWO_BY_TWO != 0 {
+            size += 8;
+        }
+        stream.skip(size)?;
+        if flags & MORE_COMPONENTS == 0 {
+            break;
+        }
+    }
+    Ok((&stream.data[start..stream.pos], has_instructions))
+}
+
+/// Decode the coordinate deltas of a point from the triplet encoding.
+fn triplet(flag: u8, stream: &mut Reader) -> anyhow::Result<(i32, i32)> {
+    let signed = |flag: u8, value: i32| if flag & 1 != 0 { value } else { -value };
+    let flag_i = flag as i32;
+    Ok(match flag {
+        0..10 => {
+            let b0 = stream.u8()? as i32;
+            (0, signed(flag, ((flag_i & 14) << 7) + b0))
+        }
+        10..20 => {
+            let b0 = stream.u8()? as i32;
+            (signed(flag, (((flag_i - 10) & 14) << 7) + b0), 0)
+        }
+        20..84 => {
+            let b0 = flag_i - 20;
+            let b1 = stream.u8()? as i32;
+            (
+                signed(flag, 1 + (b0 & 0x30) + (b1 >> 4)),
+                signed(flag >> 1, 1 + ((b0 & 0x0c) << 2) + (b1 & 0x0f)),
+            )
+        }
+        84..120 => {
+            let b0 = flag_i - 84;
+            let b1 = stream.u8()? as i32;
+            let b2 = stream.u8()? as i32;
+            (
+                signed(flag, 1 + ((b0 / 12) << 8) + b1),
+                signed(flag >> 1, 1 + (((b0 % 12) >> 2) << 8) + b2),
+            )
+        }
+        120..124 => {
+            let b1 = stream.u8()? as i32;
+            let b2 = stream.u8()? as i32;
+            let b3 = stream.u8()? as i32;
+            (
+                signed(flag, (b1 << 4) + (b2 >> 4)),
+                signed(flag >> 1, ((b2 & 0x0f) << 8) + b3),
+            )
+        }
+        _ => {
+            let x = stream.u16()? as i32;
+            let y = stream.u16()? as i32;
+            (signed(flag, x), signed(flag >> 1, y))
+        }
+    })
+}
+
+/// Write the flags and coordinates of a simple glyph, one flag per point.
+fn simple_points(points: &[(i32, i32, bool)], overlap: bool, out: &mut Vec<u8>) {
+    const ON_CURVE: u8 = 0x01;
+    const X_SHORT: u8 = 0x02;
+    const Y_SHORT: u8 = 0x04;
+    const X_SAME_OR_POSITIVE: u8 = 0x10;
+    const Y_SAME_OR_POSITIVE: u8 = 0x20;
+    const OVERLAP_SIMPLE: u8 = 0x40;
+
+    let mut flags = vec![];
+    let mut xs = vec![];
+    let mut ys = vec![];
+    let (mut last_x, mut last_y) = (0, 0);
+    for (i, &(x, y, on_curve)) in points.iter().enumerate() {
+        let mut flag = if on_curve { ON_CURVE } else { 0 };
+        if overlap && i == 0 {
+            flag |= OVERLAP_SIMPLE;
+        }
+        for (delta, short, same, coordinates) in [
+            (x - last_x, X_SHORT, X_SAME_OR_POSITIVE, &mut xs),
+            (y - last_y, Y_SHORT, Y_SAME_OR_POSITIVE, &mut ys),
+        ] {
+            if delta == 0 {
+                flag |= same;
+            } else if delta.abs() < 256 {
+                flag |= short;
+                if delta > 0 {
+                    flag |= same;
+                }
+                coordinates.push(delta.unsigned_abs() as u8);
+            } else {
+                coordinates.extend((delta as i16).to_be_bytes());
+            }
+        }
+        flags.push(flag);
+        (last_x, last_y) = (x, y);
+    }
+    out.extend(flags);
+    out.extend(xs);
+    out.extend(ys);
+}
+
+/// Rebuild the hmtx table, taking the left side bearings it leaves out from the glyphs.
+fn decode_hmtx(table: &[u8], num_h_metrics: usize, x_mins: &[i16]) -> anyhow::Result<Vec<u8>> {
+    let mut stream = Reader::new(table);
+    let flags = stream.u8()?;
+    let mut advances = vec![];
+    for _ in 0..num_h_metrics {
+        advances.push(stream.u16()?);
+    }
+    let mut out = vec![];
+    let mut lsbs = vec![];
+    for (i, &x_min) in x_mins.iter().enumerate() {
+        let omitted = if i < num_h_metrics {
+            flags & 1 != 0
+        } else {
+            flags & 2 != 0
+        };
+        lsbs.push(if omitted { x_min } else { stream.i16()? });
+    }
+    for (i, lsb) in lsbs.iter().enumerate() {
+        if let Some(advance) = advances.get(i) {
+            out.extend(advance.to_be_bytes());
+        }
+        out.extend(lsb.to_be_bytes());
+    }
+    Ok(out)
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Reader { data, pos: 0 }
+    }
+
+    fn at(data: &'a [u8], pos: usize) -> Self {
+        Reader { data, pos }
+    }
+
+    fn bytes(&mut self, len: usize) -> anyhow::Result<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| anyhow!("WOFF2 data is truncated"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn skip(&mut self, len: usize) -> anyhow::Result<()> {
+        self.bytes(len).map(|_| ())
+    }
+
+    fn u8(&mut self) -> anyhow::Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> anyhow::Result<u16> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn i16(&mut self) -> anyhow::Result<i16> {
+        Ok(self.u16()? as i16)
+    }
+
+    fn u32(&mut self) -> anyhow::Result<u32> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// A UIntBase128, at most five bytes of seven bits each.
+    fn base128(&mut self) -> anyhow::Result<u32> {
+        let mut value = 0u32;
+        for i in 0..5 {
+            let byte = self.u8()?;
+            if i == 0 && byte == 0x80 {
+                bail!("WOFF2 number has leading zeros");
+            }
+            if value & 0xfe00_0000 != 0 {
+                bail!("WOFF2 number overflows");
+            }
+            value = (value << 7) | (byte & 0x7f) as u32;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        bail!("WOFF2 number is too long")
+    }
+
+    /// A 255UInt16.
+    fn u255(&mut self) -> anyhow::Result<u16> {
+        Ok(match self.u8()? {
+            253 => self.u16()?,
+            254 => self.u8()? as u16 + 506,
+            255 => self.u8()? as u16 + 253,
+            code => code as u16,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn be16(values: &[i32]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|v| (*v as u16).to_be_bytes())
+            .collect()
+    }
+
+    fn head() -> Vec<u8> {
+        (0..54).collect()
+    }
+
+    fn hhea() -> Vec<u8> {
+        let mut hhea = vec![0; 36];
+        hhea[..4].copy_from_slice(&[0, 1, 0, 0]);
+        // numberOfHMetrics
+        hhea[35] = 2;
+        hhea
+    }
+
+    fn maxp() -> Vec<u8> {
+        vec![0, 0, 0x50, 0, 0, 3]
+    }
+
+    /// An empty glyph, a triangle with an off-curve point and two bytes of
+    /// instructions, and that triangle as a composite moved by (5, 5).
+    fn woff2() -> Vec<u8> {
+        let mut glyf = be16(&[0, 0, 3, 0]);
+        let streams: [Vec<u8>; 7] = [
+            be16(&[0, 1, -1]),
+            vec![3],
+            // x only, x only with 256 added, and x and y in three bytes, off-curve
+            vec![11, 13, 122 | 0x80],
+            vec![10, 34, 9, 0x62, 88, 2],
+            vec![0, 2, 0, 1, 5, 5],
+            [vec![0x20, 0, 0, 0], be16(&[15, 5, 305, 605])].concat(),
+            vec![0xb0, 0x01],
+        ];
+        for stream in &streams {
+            glyf.extend((stream.len() as u32).to_be_bytes());
+        }
+        glyf.extend(streams.concat());
+        // Both kinds of left side bearings left out.
+        let hmtx = [vec![3], be16(&[500, 600])].concat();
+
+        let data = [head(), hhea(), maxp(), hmtx.clone(), glyf.clone()].concat();
+        // A brotli stream of one uncompressed meta-block, then an empty last one.
+        let mut compressed = ((data.len() as u32 - 1) << 4 | 1 << 20).to_le_bytes()[..3].to_vec();
+        compressed.extend(&data);
+        compressed.push(0x03);
+
+        let mut directory = vec![0x01, 54, 0x02, 36, 0x04, 6, 0x43, 10, hmtx.len() as u8];
+        directory.extend([0x0a, 44, glyf.len() as u8, 0x0b, 8, 0]);
+        let length = HEADER_SIZE + directory.len() + compressed.len();
+        let mut woff2 = b"wOF2".to_vec();
+        woff2.extend([0, 1, 0, 0]);
+        woff2.extend((length as u32).to_be_bytes());
+        woff2.extend([0, 6, 0, 0]);
+        woff2.extend(0u32.to_be_bytes());
+        woff2.extend((compressed.len() as u32).to_be_bytes());
+        woff2.resize(HEADER_SIZE, 0);
+        woff2.extend(directory);
+        woff2.extend(compressed);
+        woff2
+    }
+
+    /// The TrueType font `woff2` encodes, written out table by table.
+    fn ttf() -> Vec<u8> {
+        let triangle = [
+            be16(&[1, 10, 0, 300, 600, 2, 2]),
+            vec![0xb0, 0x01],
+            vec![0x33, 0x21, 0x02],
+            vec![10, 0x01, 0x22, 150],
+            vec![0x02, 0x58],
+            vec![0; 3],
+        ]
+        .concat();
+        let composite = [be16(&[-1, 15, 5, 305, 605, 2, 1]), vec![5, 5]].concat();
+        let tables: [(&[u8; 4], Vec<u8>); 6] = [
+            (b"glyf", [triangle, composite].concat()),
+            (b"head", head()),
+            (b"hhea", hhea()),
+            (b"hmtx", be16(&[500, 0, 600, 10, 15])),
+            (b"loca", be16(&[0, 0, 14, 22])),
+            (b"maxp", maxp()),
+        ];
+        let mut ttf = vec![0, 1, 0, 0, 0, 6, 0, 64, 0, 2, 0, 32];
+        let mut offset = 12 + 6 * 16;
+        for (tag, data) in &tables {
+            let mut padded = data.clone();
+            padded.resize(data.len().next_multiple_of(4), 0);
+            let sum = padded
+                .chunks(4)
+                .map(|w| u32::from_be_bytes([w[0], w[1], w[2], w[3]]))
+                .fold(0u32, u32::wrapping_add);
+            ttf.extend(*tag);
+            ttf.extend(sum.to_be_bytes());
+            ttf.extend((offset as u32).to_be_bytes());
+            ttf.extend((data.len() as u32).to_be_bytes());
+            offset += padded.len();
+        }
+        for (_, data) in tables {
+            ttf.extend(&data);
+            ttf.resize(ttf.len().next_multiple_of(4), 0);
+        }
+        ttf
+    }
+
+    #[test]
+    fn decodes_transformed_tables() {
+        assert_eq!(decode(&woff2()).unwrap(), ttf());
+    }
+
+    #[test]
+    fn rejects_truncated_fonts() {
+        let woff2 = woff2();
+        for len in 0..woff2.len() {
+            assert!(decode(&woff2[..len]).is_err(), "truncated to {}", len);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_fonts() {
+        let woff2 = woff2();
+        let with = |at: usize, bytes: &[u8]| {
+            let mut font = woff2.clone();
+            font[at..at + bytes.len()].copy_from_slice(bytes);
+            decode(&font)
+        };
+        assert!(with(0, b"wOFF").is_err());
+        assert!(with(4, b"ttcf").is_err());
+        assert!(with(12, &[0, 0]).is_err());
+        // A transform of head, which has none.
+        assert!(with(HEADER_SIZE, &[0x41]).is_err());
+        // The composite glyph without its bounding box, before the instructions and the
+        // end of the brotli stream.
+        let bitmap = woff2.len() - 1 - 2 - 12;
+        assert_eq!(woff2[bitmap], 0x20);
+        assert!(with(bitmap, &[0]).is_err());
+        let mut tables = woff2[..HEADER_SIZE].to_vec();
+        tables[12..14].copy_from_slice(&5000u16.to_be_bytes());
+        tables[20..24].copy_from_slice(&1u32.to_be_bytes());
+        tables.extend([0x01, 0].repeat(5000));
+        // An empty brotli stream.
+        tables.push(0x06);
+        assert!(decode(&tables).is_err());
+        // Any byte changed must give an error or a font, not a panic.
+        for at in 0..woff2.len() {
+            for byte in [0x00, 0x7f, 0x80, 0xff] {
+                let _ = with(at, &[byte]);
+            }
+        }
+    }
+}